@@ -0,0 +1,123 @@
+use serde::Serialize;
+use std::{thread, time::SystemTime};
+use teloxide::types::{ChatId, UserId};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::config::{AnalyticsSinkConfig, AppConfig};
+pub use sinks::AnalyticsSink;
+
+mod sinks;
+
+/// Pasos del embudo de verificación
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+	Joined,
+	PageOpened,
+	ProofSubmitted,
+	Verified,
+	TimedOut,
+	Kicked,
+}
+
+impl EventKind {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Joined => "telegram integration join",
+			Self::PageOpened => "telegram integration page opened",
+			Self::ProofSubmitted => "telegram integration proof submitted",
+			Self::Verified => "telegram integration verification",
+			Self::TimedOut => "telegram integration timeout",
+			Self::Kicked => "telegram integration kick",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalyticsEvent {
+	pub kind: EventKind,
+	pub chat_id: ChatId,
+	pub user_id: UserId,
+	pub lang: Option<String>,
+	pub credential_type: Option<String>,
+	#[serde(with = "humantime_serde")]
+	pub timestamp: SystemTime,
+}
+
+impl AnalyticsEvent {
+	pub fn new(kind: EventKind, chat_id: ChatId, user_id: UserId) -> Self {
+		Self {
+			kind,
+			chat_id,
+			user_id,
+			lang: None,
+			credential_type: None,
+			timestamp: SystemTime::now(),
+		}
+	}
+
+	pub fn lang(mut self, lang: &str) -> Self {
+		self.lang = Some(lang.to_string());
+		self
+	}
+
+	pub fn credential_type(mut self, credential_type: &str) -> Self {
+		self.credential_type = Some(credential_type.to_string());
+		self
+	}
+}
+
+/// Envía eventos de analítica en segundo plano, sin bloquear ni hacer fallar la verificación
+pub struct Analytics {
+	sender: Option<UnboundedSender<AnalyticsEvent>>,
+}
+
+impl Analytics {
+	pub fn new(config: &AppConfig) -> Self {
+		// `posthog_token` se mantiene como atajo para configurar un destino de PostHog
+		let mut sinks = config.analytics.clone();
+		if let Some(token) = config.posthog_token.clone() {
+			sinks.push(AnalyticsSinkConfig::Posthog { token });
+		}
+
+		if sinks.is_empty() {
+			return Self { sender: None };
+		}
+
+		let (sender, mut receiver) = mpsc::unbounded_channel::<AnalyticsEvent>();
+
+		// Los destinos pueden bloquear (el cliente de PostHog lo hace), así que viven en su propio hilo
+		thread::Builder::new()
+			.name("analytics".to_string())
+			.spawn(move || {
+				let mut sinks = sinks
+					.iter()
+					.filter_map(|config| {
+						config
+							.build()
+							.map_err(|e| log::error!("Failed to set up analytics sink {config:?}: {e}"))
+							.ok()
+					})
+					.collect::<Vec<_>>();
+
+				while let Some(event) = receiver.blocking_recv() {
+					for sink in &mut sinks {
+						if let Err(e) = sink.send(&event) {
+							log::error!("Failed to send event to {}: {e}", sink.name());
+						}
+					}
+				}
+			})
+			.expect("Failed to spawn analytics thread");
+
+		Self {
+			sender: Some(sender),
+		}
+	}
+
+	pub fn track(&self, event: AnalyticsEvent) {
+		if let Some(sender) = &self.sender {
+			sender.send(event).ok();
+		}
+	}
+}
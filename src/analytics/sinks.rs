@@ -0,0 +1,129 @@
+use reqwest::blocking::Client;
+use serde_json::json;
+use std::{
+	collections::HashMap,
+	error::Error,
+	fs::{File, OpenOptions},
+	io::Write,
+	path::Path,
+	time::Duration,
+};
+use url::Url;
+
+use super::AnalyticsEvent;
+use crate::config::AnalyticsSinkConfig;
+
+pub type SinkResult = Result<(), Box<dyn Error>>;
+
+/// Destino de los eventos de analítica. Se ejecuta en el hilo de analítica,
+/// así que las implementaciones pueden bloquear.
+pub trait AnalyticsSink: Send {
+	fn name(&self) -> &'static str;
+
+	fn send(&mut self, event: &AnalyticsEvent) -> SinkResult;
+}
+
+impl AnalyticsSinkConfig {
+	pub fn build(&self) -> Result<Box<dyn AnalyticsSink>, Box<dyn Error>> {
+		Ok(match self {
+			Self::Posthog { token } => Box::new(PosthogSink::new(token)),
+			Self::Jsonl { path } => Box::new(JsonlSink::open(path)?),
+			Self::Webhook { url, headers } => Box::new(WebhookSink::new(url.clone(), headers.clone())),
+		})
+	}
+}
+
+pub struct PosthogSink {
+	client: posthog_rs::Client,
+}
+
+impl PosthogSink {
+	pub fn new(token: &str) -> Self {
+		Self {
+			client: posthog_rs::client(token),
+		}
+	}
+}
+
+impl AnalyticsSink for PosthogSink {
+	fn name(&self) -> &'static str {
+		"posthog"
+	}
+
+	fn send(&mut self, event: &AnalyticsEvent) -> SinkResult {
+		let mut posthog_event =
+			posthog_rs::Event::new(event.kind.name().to_string(), event.user_id.to_string());
+		posthog_event.insert_prop("chat_id", event.chat_id.0)?;
+		posthog_event.insert_prop("language", &event.lang)?;
+		posthog_event.insert_prop("credential_type", &event.credential_type)?;
+
+		Ok(self.client.capture(posthog_event)?)
+	}
+}
+
+/// Añade cada evento como una línea JSON a un archivo local
+pub struct JsonlSink {
+	file: File,
+}
+
+impl JsonlSink {
+	pub fn open(path: &Path) -> std::io::Result<Self> {
+		Ok(Self {
+			file: OpenOptions::new().create(true).append(true).open(path)?,
+		})
+	}
+}
+
+impl AnalyticsSink for JsonlSink {
+	fn name(&self) -> &'static str {
+		"jsonl"
+	}
+
+	fn send(&mut self, event: &AnalyticsEvent) -> SinkResult {
+		let mut line = serde_json::to_vec(event)?;
+		line.push(b'\n');
+
+		Ok(self.file.write_all(&line)?)
+	}
+}
+
+/// Envía cada evento como `POST` JSON a una URL arbitraria
+pub struct WebhookSink {
+	url: Url,
+	client: Client,
+	headers: HashMap<String, String>,
+}
+
+impl WebhookSink {
+	pub fn new(url: Url, headers: HashMap<String, String>) -> Self {
+		Self {
+			url,
+			headers,
+			client: Client::builder()
+				.timeout(Duration::from_secs(5))
+				.build()
+				.expect("Failed to build HTTP client"),
+		}
+	}
+}
+
+impl AnalyticsSink for WebhookSink {
+	fn name(&self) -> &'static str {
+		"webhook"
+	}
+
+	fn send(&mut self, event: &AnalyticsEvent) -> SinkResult {
+		let mut request = self
+			.client
+			.post(self.url.clone())
+			.header("User-Agent", "World ID Telegram Bot/1.0")
+			.json(&json!({ "event": event.kind.name(), "properties": event }));
+
+		for (name, value) in &self.headers {
+			request = request.header(name, value);
+		}
+
+		request.send()?.error_for_status()?;
+		Ok(())
+	}
+}
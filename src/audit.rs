@@ -0,0 +1,373 @@
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeSet,
+	fmt,
+	fs::{self, File, OpenOptions},
+	io::{BufRead, BufReader, Write},
+	path::Path,
+	sync::{Arc, Mutex},
+	thread,
+	time::{Duration, SystemTime},
+};
+use teloxide::types::{ChatId, UserId};
+use tokio::sync::mpsc;
+
+use crate::config::AuditConfig;
+
+/// Cada cuánto se aplica la retención además de al iniciar
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Quién realizó una acción de moderación
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Actor {
+	/// El propio bot, por una prueba de World ID o al expirar el plazo
+	Bot,
+	/// Un operador a través de la API de administración
+	AdminApi,
+	/// Un administrador del grupo desde el panel web
+	Dashboard(UserId),
+	/// Un administrador del grupo desde Telegram (botones del bot)
+	Admin(UserId),
+	/// El propio usuario pendiente (por ejemplo, al pedir más tiempo)
+	User(UserId),
+}
+
+impl fmt::Display for Actor {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Bot => write!(f, "bot"),
+			Self::AdminApi => write!(f, "admin API"),
+			Self::Dashboard(user_id) => write!(f, "admin {user_id} (dashboard)"),
+			Self::Admin(user_id) => write!(f, "admin {user_id}"),
+			Self::User(user_id) => write!(f, "user {user_id}"),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+	Joined,
+	/// Usuario silenciado hasta que se verifique
+	Restricted,
+	Verified,
+	Approved,
+	Rejected,
+	/// Plazo de verificación extendido
+	Extended,
+	TimedOut,
+	Kicked,
+	/// Usuario baneado manualmente
+	Banned,
+	/// Mensaje eliminado del grupo: del bot, o de un usuario sin verificar
+	MessageDeleted,
+	/// Prueba de World ID rechazada
+	Failed,
+	/// Restricción levantada sin verificar, porque no se pudo enviar la bienvenida
+	Released,
+}
+
+impl AuditAction {
+	pub const fn as_str(&self) -> &'static str {
+		match self {
+			Self::Joined => "joined",
+			Self::Restricted => "restricted",
+			Self::Verified => "verified",
+			Self::Approved => "approved",
+			Self::Rejected => "rejected",
+			Self::Extended => "extended",
+			Self::TimedOut => "timed_out",
+			Self::Kicked => "kicked",
+			Self::Banned => "banned",
+			Self::MessageDeleted => "message_deleted",
+			Self::Failed => "failed",
+			Self::Released => "released",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+	pub actor: Actor,
+	pub action: AuditAction,
+	pub chat_id: ChatId,
+	pub user_id: UserId,
+	pub reason: Option<String>,
+	#[serde(with = "humantime_serde")]
+	pub timestamp: SystemTime,
+}
+
+impl AuditEntry {
+	pub fn new(actor: Actor, action: AuditAction, chat_id: ChatId, user_id: UserId) -> Self {
+		Self {
+			actor,
+			action,
+			chat_id,
+			user_id,
+			reason: None,
+			timestamp: SystemTime::now(),
+		}
+	}
+
+	pub fn reason(mut self, reason: &str) -> Self {
+		self.reason = Some(reason.to_string());
+		self
+	}
+}
+
+/// Filtros para consultar el registro; los campos vacíos no filtran
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+	pub chat_id: Option<ChatId>,
+	pub user_id: Option<UserId>,
+	pub action: Option<AuditAction>,
+	/// Solo entradas más recientes que esta antigüedad
+	pub since: Option<Duration>,
+	/// Solo las últimas `limit` entradas
+	pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+	pub fn chat(chat_id: ChatId) -> Self {
+		Self {
+			chat_id: Some(chat_id),
+			..Self::default()
+		}
+	}
+
+	fn matches(&self, entry: &AuditEntry, now: SystemTime) -> bool {
+		self.chat_id.is_none_or(|chat_id| entry.chat_id == chat_id)
+			&& self.user_id.is_none_or(|user_id| entry.user_id == user_id)
+			&& self.action.is_none_or(|action| entry.action == action)
+			&& self.since.is_none_or(|since| is_newer_than(entry, now, since))
+	}
+}
+
+/// Escritura pendiente en el archivo del registro
+#[derive(Debug)]
+enum FileOp {
+	Append(AuditEntry),
+	/// Reemplaza el archivo entero, tras aplicar la retención
+	Rewrite(Vec<AuditEntry>),
+}
+
+/// Registro de todas las acciones de moderación, guardado como JSONL.
+/// Las entradas se consultan en memoria; el archivo lo escribe un hilo propio
+#[derive(Debug)]
+pub struct AuditLog {
+	config: AuditConfig,
+	entries: Mutex<Vec<AuditEntry>>,
+	/// `None` si el registro es solo en memoria
+	writer: Option<mpsc::UnboundedSender<FileOp>>,
+}
+
+impl AuditLog {
+	/// Carga las entradas guardadas y aplica la retención configurada.
+	/// Si el archivo no se puede usar, el registro sigue funcionando solo en memoria
+	pub fn open(config: &AuditConfig) -> Self {
+		let mut config = config.clone();
+		config.path = config.path.filter(|path| !path.as_os_str().is_empty());
+
+		let Some(path) = config.path.clone() else {
+			return Self {
+				config,
+				entries: Mutex::new(Vec::new()),
+				writer: None,
+			};
+		};
+
+		let entries = match File::open(&path) {
+			Ok(file) => {
+				let entries = BufReader::new(file)
+					.lines()
+					.map_while(Result::ok)
+					.filter_map(|line| serde_json::from_str(&line).ok())
+					.collect::<Vec<AuditEntry>>();
+
+				log::info!("Loaded {} audit log entries from {}", entries.len(), path.display());
+				entries
+			},
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+			Err(e) => {
+				log::error!("Failed to read the audit log {}: {e}", path.display());
+				Vec::new()
+			},
+		};
+
+		// Como la analítica, el archivo se escribe en su propio hilo para no bloquear a quien registra
+		let (writer, receiver) = mpsc::unbounded_channel();
+		thread::Builder::new()
+			.name("audit".to_string())
+			.spawn(move || write_file(&path, receiver))
+			.expect("Failed to spawn audit log thread");
+
+		let log = Self {
+			config,
+			entries: Mutex::new(entries),
+			writer: Some(writer),
+		};
+		log.enforce_retention();
+		log
+	}
+
+	pub fn record(&self, entry: AuditEntry) {
+		log::info!(
+			"[audit] {:?} {:?} user {} in chat {}{}",
+			entry.actor,
+			entry.action,
+			entry.user_id,
+			entry.chat_id,
+			entry
+				.reason
+				.as_deref()
+				.map(|reason| format!(" ({reason})"))
+				.unwrap_or_default()
+		);
+
+		// Se encola con las entradas bloqueadas, para que el archivo siga el mismo orden
+		let mut entries = self.entries.lock().unwrap();
+		self.write(FileOp::Append(entry.clone()));
+		entries.push(entry);
+	}
+
+	/// Entradas que cumplen el filtro, de la más antigua a la más reciente
+	pub fn query(&self, filter: &AuditFilter) -> Vec<AuditEntry> {
+		let now = SystemTime::now();
+		let entries = self.entries.lock().unwrap();
+
+		let mut entries = entries
+			.iter()
+			.filter(|entry| filter.matches(entry, now))
+			.cloned()
+			.collect::<Vec<_>>();
+
+		if let Some(limit) = filter.limit {
+			entries.drain(..entries.len().saturating_sub(limit));
+		}
+
+		entries
+	}
+
+	/// Las últimas `limit` entradas que cumplen `keep`, de la más reciente a la más antigua,
+	/// copiando solo esas
+	pub fn latest(&self, limit: usize, keep: impl Fn(&AuditEntry) -> bool) -> Vec<AuditEntry> {
+		let entries = self.entries.lock().unwrap();
+
+		entries
+			.iter()
+			.rev()
+			.filter(|entry| keep(entry))
+			.take(limit)
+			.cloned()
+			.collect()
+	}
+
+	/// Grupos distintos con alguna entrada, sin copiar las entradas
+	pub fn chat_ids(&self) -> Vec<ChatId> {
+		let entries = self.entries.lock().unwrap();
+
+		entries
+			.iter()
+			.map(|entry| entry.chat_id.0)
+			.collect::<BTreeSet<_>>()
+			.into_iter()
+			.map(ChatId)
+			.collect()
+	}
+
+	/// Aplica la retención periódicamente
+	pub async fn run_retention(self: Arc<Self>) {
+		let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+		interval.tick().await;
+
+		loop {
+			interval.tick().await;
+			self.enforce_retention();
+		}
+	}
+
+	/// Descarta las entradas más antiguas que `retention` o que sobran de `max_entries`,
+	/// y manda reescribir el archivo con las que quedan
+	fn enforce_retention(&self) {
+		let mut entries = self.entries.lock().unwrap();
+		let before = entries.len();
+
+		if let Some(retention) = self.config.retention {
+			let now = SystemTime::now();
+			entries.retain(|entry| is_newer_than(entry, now, retention));
+		}
+		if let Some(max_entries) = self.config.max_entries {
+			let excess = entries.len().saturating_sub(max_entries);
+			entries.drain(..excess);
+		}
+
+		// Reescribir solo si algo caducó; si no, basta con seguir añadiendo al archivo
+		if entries.len() == before {
+			return;
+		}
+
+		log::info!("Dropped {} expired audit log entries", before - entries.len());
+		self.write(FileOp::Rewrite(entries.clone()));
+	}
+
+	fn write(&self, op: FileOp) {
+		let Some(writer) = &self.writer else {
+			return;
+		};
+
+		if writer.send(op).is_err() {
+			log::error!("The audit log writer stopped, the entry is kept only in memory");
+		}
+	}
+}
+
+/// Atiende las escrituras del registro en orden, hasta que se cierre el canal
+fn write_file(path: &Path, mut receiver: mpsc::UnboundedReceiver<FileOp>) {
+	let mut file = OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.map_err(|e| log::error!("Failed to open the audit log {}: {e}", path.display()))
+		.ok();
+
+	while let Some(op) = receiver.blocking_recv() {
+		match op {
+			FileOp::Append(entry) => {
+				let Some(file) = file.as_mut() else {
+					continue;
+				};
+
+				let line = serde_json::to_string(&entry).expect("audit entries are always serializable");
+				if let Err(e) = writeln!(file, "{line}") {
+					log::error!("Failed to persist audit log entry: {e}");
+				}
+			},
+			FileOp::Rewrite(entries) => {
+				file = rewrite_file(path, &entries)
+					.map_err(|e| log::error!("Failed to write the audit log {}: {e}", path.display()))
+					.ok();
+			},
+		}
+	}
+}
+
+/// Escribe las entradas en un archivo temporal, lo mueve sobre el registro y lo abre para añadir
+fn rewrite_file(path: &Path, entries: &[AuditEntry]) -> std::io::Result<File> {
+	let tmp_path = path.with_extension("tmp");
+
+	let mut tmp = File::create(&tmp_path)?;
+	for entry in entries {
+		writeln!(tmp, "{}", serde_json::to_string(entry)?)?;
+	}
+	tmp.sync_all()?;
+	fs::rename(&tmp_path, path)?;
+
+	OpenOptions::new().append(true).open(path)
+}
+
+fn is_newer_than(entry: &AuditEntry, now: SystemTime, age: Duration) -> bool {
+	now.duration_since(entry.timestamp)
+		.map_or(true, |elapsed| elapsed <= age)
+}
+
@@ -0,0 +1,426 @@
+use humantime_serde::re::humantime;
+use std::{sync::Arc, time::Duration};
+use teloxide::{
+	payloads::{AnswerCallbackQuerySetters, EditMessageReplyMarkupSetters},
+	requests::Requester,
+	types::{
+		CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardButtonKind,
+		InlineKeyboardMarkup, Me, UserId,
+	},
+};
+
+use crate::{
+	audit::Actor,
+	bot::{ban, is_group_admin, on_verified, reject, Bot, HandlerResult, JoinRequest, JoinRequests},
+	config::AppConfig,
+	events::Events,
+	i18n::{I18n, Translation},
+	log_channel::LogAction,
+};
+
+/// Prefijo de los botones de administración del mensaje de bienvenida
+const WELCOME_PREFIX: &str = "welcome";
+
+/// Botones del mensaje de bienvenida, visibles para todos pero solo para administradores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WelcomeAction {
+	Approve,
+	Kick,
+	Extend,
+	/// Enlace nuevo y más tiempo, solo para el propio usuario pendiente
+	Resend,
+}
+
+impl WelcomeAction {
+	const fn as_str(&self) -> &'static str {
+		match self {
+			Self::Approve => "approve",
+			Self::Kick => "kick",
+			Self::Extend => "extend",
+			Self::Resend => "resend",
+		}
+	}
+
+	fn callback_data(&self, user_id: UserId) -> String {
+		format!("{WELCOME_PREFIX}:{}:{user_id}", self.as_str())
+	}
+
+	/// Botón que va junto al de verificación del usuario
+	pub fn resend_button(translation: &Translation, user_id: UserId) -> InlineKeyboardButton {
+		InlineKeyboardButton::callback(
+			&translation.resend_button,
+			Self::Resend.callback_data(user_id),
+		)
+	}
+
+	/// Fila de botones que va debajo del botón de verificación de cada usuario.
+	/// El `callback_data` tiene el formato `welcome:<acción>:<user_id>`; el grupo es el del mensaje
+	pub fn buttons(translation: &Translation, user_id: UserId) -> Vec<InlineKeyboardButton> {
+		[
+			(Self::Approve, &translation.welcome_approve_button),
+			(Self::Kick, &translation.welcome_kick_button),
+			(Self::Extend, &translation.welcome_extend_button),
+		]
+		.into_iter()
+		.map(|(action, label)| InlineKeyboardButton::callback(label, action.callback_data(user_id)))
+		.collect()
+	}
+
+	fn parse_callback(data: &str) -> Option<(Self, UserId)> {
+		let mut parts = data.split(':');
+		if parts.next()? != WELCOME_PREFIX {
+			return None;
+		}
+
+		let action = match parts.next()? {
+			"approve" => Self::Approve,
+			"kick" => Self::Kick,
+			"extend" => Self::Extend,
+			"resend" => Self::Resend,
+			_ => return None,
+		};
+
+		Some((action, UserId(parts.next()?.parse().ok()?)))
+	}
+}
+
+/// Acción pedida por un administrador desde cualquiera de los botones
+#[derive(Debug, Clone, Copy)]
+enum Moderation {
+	Approve,
+	Kick,
+	Ban,
+	/// Extiende el plazo de verificación en la duración indicada
+	Extend(Duration),
+}
+
+/// Resultado de la acción, para elegir el aviso
+enum Outcome {
+	Done,
+	Extended(Duration),
+	NotPending,
+}
+
+/// Botones de los mensajes del bot: los de administración del mensaje de bienvenida
+/// y los de "aprobar"/"banear" del canal de registro
+pub async fn callback_handler(
+	bot: Bot,
+	q: CallbackQuery,
+	me: Me,
+	config: Arc<AppConfig>,
+	join_requests: JoinRequests,
+	i18n: Arc<I18n>,
+	events: Events,
+) -> HandlerResult {
+	let Some(data) = q.data.as_deref() else {
+		return Ok(());
+	};
+
+	// En el canal de registro el grupo va en el botón; en la bienvenida es el chat del mensaje
+	let (moderation, chat_id, user_id, from_welcome) =
+		if let Some((action, chat_id, user_id)) = LogAction::parse_callback(data) {
+			let moderation = match action {
+				LogAction::Approve => Moderation::Approve,
+				LogAction::Ban => Moderation::Ban,
+			};
+			(moderation, chat_id, user_id, false)
+		} else if let Some((action, user_id)) = WelcomeAction::parse_callback(data) {
+			let Some(message) = &q.message else {
+				return Ok(());
+			};
+			let moderation = match action {
+				WelcomeAction::Approve => Moderation::Approve,
+				WelcomeAction::Kick => Moderation::Kick,
+				WelcomeAction::Extend => {
+					Moderation::Extend(config.groups_config.get(message.chat.id).ban_after)
+				},
+				WelcomeAction::Resend => {
+					let key = (message.chat.id, user_id);
+					return resend_link(&bot, &q, &me, key, &config, &join_requests, &i18n, &events)
+						.await;
+				},
+			};
+			(moderation, message.chat.id, user_id, true)
+		} else {
+			return Ok(());
+		};
+
+	let translation = i18n.get(i18n.detect_language(Some(&q.from)));
+
+	// Solo los administradores del grupo afectado pueden usar los botones
+	if !is_group_admin(&bot, &config, chat_id, q.from.id).await? {
+		bot.answer_callback_query(q.id)
+			.text(&translation.callback_admin_only)
+			.show_alert(true)
+			.await?;
+		return Ok(());
+	}
+
+	let actor = Actor::Admin(q.from.id);
+	let result = moderate(
+		&bot,
+		moderation,
+		(chat_id, user_id),
+		&join_requests,
+		&events,
+		actor,
+	)
+	.await;
+
+	let toast = match &result {
+		Ok(Outcome::Done) => translation.callback_done.clone(),
+		Ok(Outcome::Extended(by)) => translation
+			.callback_extended
+			.replace("{TIME}", &humantime::format_duration(*by).to_string()),
+		Ok(Outcome::NotPending) => translation.callback_not_pending.clone(),
+		Err(e) => {
+			log::error!("Failed to {moderation:?} {user_id} in {chat_id}: {e:?}");
+			translation.alert_error.clone()
+		},
+	};
+	bot.answer_callback_query(q.id).text(toast).await?;
+
+	if !matches!(result, Ok(Outcome::Done)) {
+		return Ok(());
+	}
+
+	// Quitar los botones ya resueltos, para que nadie repita la acción
+	let Some(message) = &q.message else {
+		return Ok(());
+	};
+	let markup = match message.reply_markup() {
+		Some(markup) if from_welcome => without_user_rows(markup, user_id),
+		_ => InlineKeyboardMarkup::default(),
+	};
+
+	// El mensaje de bienvenida puede haberse eliminado si no quedaban usuarios pendientes
+	if let Err(e) = bot
+		.edit_message_reply_markup(message.chat.id, message.id)
+		.reply_markup(markup)
+		.await
+	{
+		log::debug!("Couldn't update the buttons of message {}: {e}", message.id);
+	}
+
+	Ok(())
+}
+
+/// El usuario pendiente pide un enlace nuevo: se regenera su token, se extiende el plazo
+/// y se abre el chat privado con el bot, donde continúa la verificación
+#[allow(clippy::too_many_arguments)]
+async fn resend_link(
+	bot: &Bot,
+	q: &CallbackQuery,
+	me: &Me,
+	(chat_id, user_id): (ChatId, UserId),
+	config: &AppConfig,
+	join_requests: &JoinRequests,
+	i18n: &I18n,
+	events: &Events,
+) -> HandlerResult {
+	let translation = i18n.get(i18n.detect_language(Some(&q.from)));
+	let settings = config.groups_config.get(chat_id);
+
+	if q.from.id != user_id {
+		let messages = settings.messages.resolve(translation);
+		bot.answer_callback_query(q.id.clone())
+			.text(&messages.user_doesnt_match_error)
+			.await?;
+		return Ok(());
+	}
+	let refreshed = join_requests
+		.get_mut(&(chat_id, user_id))
+		.filter(|req| !req.is_verified)
+		.map(|mut req| {
+			if req.extensions >= settings.max_extensions {
+				return None;
+			}
+
+			// El nuevo token invalida el enlace anterior
+			req.extensions += 1;
+			req.expires_at += settings.ban_after;
+			req.token = JoinRequest::generate_token();
+			Some(req.token.clone())
+		});
+
+	let token = match refreshed {
+		None => {
+			bot.answer_callback_query(q.id.clone())
+				.text(&translation.callback_not_pending)
+				.await?;
+			return Ok(());
+		},
+		Some(None) => {
+			bot.answer_callback_query(q.id.clone())
+				.text(&translation.resend_limit_reached)
+				.show_alert(true)
+				.await?;
+			return Ok(());
+		},
+		Some(Some(token)) => token,
+	};
+
+	events.extended(chat_id, user_id, Actor::User(user_id), settings.ban_after);
+
+	let mut url = me.tme_url();
+	url.query_pairs_mut()
+		.append_pair("start", &format!("verify_{token}"));
+
+	bot.answer_callback_query(q.id.clone())
+		.text(
+			translation
+				.resend_done
+				.replace("{TIME}", &humantime::format_duration(settings.ban_after).to_string()),
+		)
+		.url(url)
+		.await?;
+
+	Ok(())
+}
+
+async fn moderate(
+	bot: &Bot,
+	moderation: Moderation,
+	(chat_id, user_id): (ChatId, UserId),
+	join_requests: &JoinRequests,
+	events: &Events,
+	actor: Actor,
+) -> Result<Outcome, Box<dyn std::error::Error + Send + Sync>> {
+	let is_pending = join_requests
+		.get(&(chat_id, user_id))
+		.is_some_and(|req| !req.is_verified);
+
+	match moderation {
+		// Banear también cierra la solicitud si seguía pendiente; expulsar le deja volver a unirse
+		Moderation::Ban => {
+			ban(bot, chat_id, user_id, join_requests, events, actor).await?;
+		},
+		_ if !is_pending => return Ok(Outcome::NotPending),
+		Moderation::Approve => {
+			on_verified(
+				bot.clone(),
+				chat_id,
+				user_id,
+				join_requests.clone(),
+				events.clone(),
+				actor,
+			)
+			.await?;
+		},
+		Moderation::Kick => {
+			reject(bot, chat_id, user_id, join_requests, events, actor).await?;
+		},
+		Moderation::Extend(by) => {
+			let Some(mut join_req) = join_requests.get_mut(&(chat_id, user_id)) else {
+				return Ok(Outcome::NotPending);
+			};
+
+			join_req.expires_at += by;
+			events.extended(chat_id, user_id, actor, by);
+			return Ok(Outcome::Extended(by));
+		},
+	}
+
+	Ok(Outcome::Done)
+}
+
+/// Quita la fila de administración de `user_id` y su botón de verificación, que va justo encima
+fn without_user_rows(markup: &InlineKeyboardMarkup, user_id: UserId) -> InlineKeyboardMarkup {
+	let mut rows = markup.inline_keyboard.clone();
+
+	// El botón de reenvío va en la fila de verificación; se busca la de administración
+	let is_admin_row = |row: &Vec<InlineKeyboardButton>| {
+		row.iter().any(|button| match &button.kind {
+			InlineKeyboardButtonKind::CallbackData(data) => WelcomeAction::parse_callback(data)
+				.is_some_and(|(action, id)| id == user_id && action != WelcomeAction::Resend),
+			_ => false,
+		})
+	};
+
+	if let Some(index) = rows.iter().position(is_admin_row) {
+		rows.drain(index.saturating_sub(1)..=index);
+	}
+
+	InlineKeyboardMarkup::new(rows)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn welcome_markup(translation: &Translation, users: &[UserId]) -> InlineKeyboardMarkup {
+		let url = url::Url::parse("https://t.me/bot").unwrap();
+		let rows = users.iter().flat_map(|&user_id| {
+			let verify = vec![
+				InlineKeyboardButton::url("verify", url.clone()),
+				WelcomeAction::resend_button(translation, user_id),
+			];
+			[verify, WelcomeAction::buttons(translation, user_id)]
+		});
+
+		InlineKeyboardMarkup::new(rows)
+	}
+
+	#[test]
+	fn parses_own_callback_data() {
+		let actions = [
+			WelcomeAction::Approve,
+			WelcomeAction::Kick,
+			WelcomeAction::Extend,
+			WelcomeAction::Resend,
+		];
+		for action in actions {
+			let data = action.callback_data(UserId(42));
+			assert_eq!(
+				WelcomeAction::parse_callback(&data),
+				Some((action, UserId(42)))
+			);
+		}
+	}
+
+	#[test]
+	fn rejects_foreign_or_malformed_callback_data() {
+		for data in [
+			"log:approve:-100:42",
+			"welcome:ban:42",
+			"welcome:kick",
+			"welcome:kick:ada",
+			"",
+		] {
+			assert_eq!(WelcomeAction::parse_callback(data), None, "{data}");
+		}
+	}
+
+	#[test]
+	fn removes_verify_and_admin_rows_of_single_user() {
+		let translation = Translation::default();
+		let markup = welcome_markup(&translation, &[UserId(1)]);
+
+		assert!(without_user_rows(&markup, UserId(1))
+			.inline_keyboard
+			.is_empty());
+	}
+
+	#[test]
+	fn keeps_rows_of_other_users() {
+		let translation = Translation::default();
+		let users = [UserId(1), UserId(2), UserId(3)];
+		let markup = welcome_markup(&translation, &users);
+
+		let remaining = without_user_rows(&markup, UserId(2));
+
+		let expected = welcome_markup(&translation, &[UserId(1), UserId(3)]);
+		assert_eq!(remaining.inline_keyboard, expected.inline_keyboard);
+	}
+
+	#[test]
+	fn unknown_user_leaves_markup_untouched() {
+		let translation = Translation::default();
+		let markup = welcome_markup(&translation, &[UserId(1)]);
+
+		assert_eq!(
+			without_user_rows(&markup, UserId(9)).inline_keyboard,
+			markup.inline_keyboard
+		);
+	}
+}
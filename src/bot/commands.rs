@@ -1,18 +1,26 @@
+use humantime_serde::re::humantime;
 use std::sync::Arc;
 use teloxide::{
 	payloads::SendMessageSetters,
 	requests::Requester,
-	types::{Me, Message},
-	utils::command::BotCommands,
-	Bot,
+	types::{Me, Message, ParseMode},
+	utils::{command::BotCommands, html},
 };
 
 use crate::{
-	bot::HandlerResult,
-	config::{AppConfig, GroupsConfig},
+	audit::AuditFilter,
+	bot::{is_group_admin, join_check, Bot, HandlerResult, JoinRequests},
+	config::{AppConfig, AutoDeleteSettings, GroupsConfig},
+	events::Events,
 	i18n::I18n, // AÑADIR
+	jobs::Jobs,
+	permissions::{missing_permissions, Permission},
 };
 
+/// Entradas que muestra `/log` por defecto y como máximo
+const LOG_DEFAULT_ENTRIES: usize = 10;
+const LOG_MAX_ENTRIES: usize = 50;
+
 #[derive(BotCommands)]
 #[command(rename_rule = "lowercase", description = "Available commands:")]
 pub enum Command {
@@ -21,22 +29,28 @@ pub enum Command {
 	#[command(description = "Check that the bot is online and has the right permissions.")]
 	Check,
 	#[command(description = "Initial help when talking to the bot for the first time.")]
-	Start,
+	Start(String),
+	#[command(description = "Show the latest moderation actions in this group (admins only).")]
+	Log(String),
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn command_handler(
 	bot: Bot,
 	config: Arc<AppConfig>,
 	msg: Message,
 	me: Me,
 	text: String,
+	join_requests: JoinRequests,
 	i18n: Arc<I18n>, // AÑADIR ESTE PARÁMETRO
+	events: Events,
+	jobs: Jobs,
 ) -> HandlerResult {
 	if msg.from().is_none() {
 		return Ok(());
 	}
 	
-	if !config.groups_config.is_group_allowed(msg.chat.id) {
+	if !msg.chat.is_private() && !config.groups_config.is_group_allowed(msg.chat.id) {
 		return on_group_not_allowed(bot, &config.groups_config, msg, i18n).await; // MODIFICAR
 	}
 	
@@ -48,12 +62,12 @@ pub async fn command_handler(
 	let group_settings = config.groups_config.get(msg.chat.id);
 	let lang = group_settings
 		.language
-		.as_ref()
-		.map(|s| s.as_str())
+		.as_deref()
 		.unwrap_or_else(|| i18n.detect_language(msg.from()));
 	let translation = i18n.get(lang);
 	
-	match command {
+	// Respuesta del bot y si es un aviso de error, para poder borrarla después
+	let reply = match command {
 		Command::Check => {
 			if msg.chat.is_private() {
 				bot.send_message(msg.chat.id, &translation.help_use_in_group)
@@ -62,33 +76,135 @@ pub async fn command_handler(
 				return Ok(());
 			}
 			
-			let is_admin = bot
-				.get_chat_member(msg.chat.id, bot.get_me().await?.id)
+			// Sin ninguno de los permisos, lo más probable es que el bot no sea administrador
+			let missing = missing_permissions(&bot, msg.chat.id, me.id)
 				.await?
-				.is_administrator();
-			
-			let message = if is_admin {
-				&translation.help_group_check_ok
+				.unwrap_or(Permission::ALL.to_vec());
+			let message = if missing.is_empty() {
+				translation.help_group_check_ok.clone()
+			} else if missing.len() == Permission::ALL.len() {
+				translation.help_group_check_fail.clone()
 			} else {
-				&translation.help_group_check_fail
+				translation
+					.help_group_check_missing
+					.replace("{PERMISSIONS}", &Permission::describe(&missing, translation))
 			};
 			
-			bot.send_message(msg.chat.id, message)
+			let sent = bot
+				.send_message(msg.chat.id, message)
+				.reply_to_message_id(msg.id)
+				.await?;
+			Some((sent, !missing.is_empty()))
+		},
+		Command::Log(_) if msg.chat.is_private() => {
+			bot.send_message(msg.chat.id, &translation.help_use_in_group)
 				.reply_to_message_id(msg.id)
 				.await?;
+			return Ok(());
 		},
-		Command::Help | Command::Start => {
+		Command::Log(count) => {
+			let Some(user) = msg.from() else {
+				return Ok(());
+			};
+
+			if !is_group_admin(&bot, &config, msg.chat.id, user.id).await? {
+				let sent = bot
+					.send_message(msg.chat.id, &translation.admin_only)
+					.reply_to_message_id(msg.id)
+					.await?;
+				schedule_deletion(&jobs, &group_settings.auto_delete, &msg, Some((sent, true)));
+				return Ok(());
+			}
+
+			let limit = count
+				.trim()
+				.parse()
+				.unwrap_or(LOG_DEFAULT_ENTRIES)
+				.clamp(1, LOG_MAX_ENTRIES);
+			let entries = events.audit.query(&AuditFilter {
+				limit: Some(limit),
+				..AuditFilter::chat(msg.chat.id)
+			});
+
+			let text = if entries.is_empty() {
+				html::escape(&translation.log_empty)
+			} else {
+				let lines = entries
+					.iter()
+					.rev()
+					.map(|entry| {
+						format!(
+							"<code>{}</code> · {} · <a href=\"tg://user?id={}\">{}</a> · {}{}",
+							humantime::format_rfc3339_seconds(entry.timestamp),
+							entry.action.as_str(),
+							entry.user_id,
+							entry.user_id,
+							html::escape(&entry.actor.to_string()),
+							entry
+								.reason
+								.as_deref()
+								.map(|reason| format!(" ({})", html::escape(reason)))
+								.unwrap_or_default()
+						)
+					})
+					.collect::<Vec<_>>()
+					.join("\n");
+
+				format!("{}\n\n{lines}", html::escape(&translation.log_header))
+			};
+
+			let sent = bot
+				.send_message(msg.chat.id, text)
+				.reply_to_message_id(msg.id)
+				.parse_mode(ParseMode::Html)
+				.await?;
+			Some((sent, false))
+		},
+		Command::Start(payload) if msg.chat.is_private() && payload.starts_with("verify_") => {
+			let token = payload.trim_start_matches("verify_");
+			return join_check::private_verification(bot, msg, token, config, join_requests, i18n)
+				.await;
+		},
+		Command::Help | Command::Start(_) => {
 			if msg.chat.is_private() {
 				bot.send_message(msg.chat.id, &translation.help_private)
 					.reply_to_message_id(msg.id)
 					.await?;
+				return Ok(());
 			}
+			None
 		},
 	};
 	
+	schedule_deletion(&jobs, &group_settings.auto_delete, &msg, reply);
+	
 	Ok(())
 }
 
+/// Programa el borrado del comando y de la respuesta del bot según `auto_delete` del grupo
+fn schedule_deletion(
+	jobs: &Jobs,
+	auto_delete: &AutoDeleteSettings,
+	command: &Message,
+	reply: Option<(Message, bool)>,
+) {
+	if let Some(delay) = auto_delete.commands {
+		jobs.delete_message(command.chat.id, command.id, delay);
+	}
+
+	let Some((reply, is_failure)) = reply else {
+		return;
+	};
+	let delay = if is_failure {
+		auto_delete.failure_notices.or(auto_delete.commands)
+	} else {
+		auto_delete.commands
+	};
+	if let Some(delay) = delay {
+		jobs.delete_message(reply.chat.id, reply.id, delay);
+	}
+}
+
 pub async fn on_group_not_allowed(
     bot: Bot,
     config: &GroupsConfig,
@@ -107,8 +223,9 @@ pub async fn on_group_not_allowed(
     let lang = i18n.detect_language(msg.from());
     let translation = i18n.get(lang);
 
-    // Enviamos mensaje de advertencia
-    bot.send_message(chat_id, &translation.unauthorized_group)
+    // Enviamos mensaje de advertencia, con el texto propio del grupo si lo tiene
+    let messages = config.get(chat_id).messages.resolve(translation);
+    bot.send_message(chat_id, &messages.unauthorized_group)
         .await?;
 
     // Si el grupo está realmente no autorizado, nos salimos
@@ -1,128 +1,708 @@
-use std::sync::Arc;
-use tokio::time::{sleep, Duration};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::time::{interval, sleep_until};
 use teloxide::{
     prelude::*,
-    types::{ChatPermissions, InlineKeyboardButton, InlineKeyboardMarkup, User},
+    types::{
+        ChatPermissions, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MessageId,
+        ParseMode, User, WebAppInfo,
+    },
     utils::html::escape,
+    RequestError,
 };
 
 use crate::{
-    bot::{commands::on_group_not_allowed, HandlerResult, JoinRequest, JoinRequests},
-    config::{AppConfig, MessagesText},
-    i18n::I18n,
+    audit::Actor,
+    bot::{
+        callbacks::WelcomeAction, commands::on_group_not_allowed, retry::send_with_retry,
+        Bot, HandlerResult, JoinRequest, JoinRequests,
+    },
+    config::{AppConfig, MediaKind, MediaSource, WelcomeMedia},
+    events::Events,
+    i18n::{I18n, Translation},
+    jobs::Jobs,
+    messages::{TemplateFormat, WelcomeContext, WelcomeTemplate},
 };
 
-/// Maneja la llegada de nuevos usuarios al grupo
+pub use queue::JoinQueue;
+pub use spam::{is_from_pending_user, pending_message_handler};
+
+mod queue;
+mod spam;
+
+/// Maneja la llegada de nuevos usuarios al grupo, encolando el aviso para procesarlo en orden
 pub async fn join_handler(
     bot: Bot,
     msg: Message,
-    users: Vec<User>,
     config: Arc<AppConfig>,
-    join_requests: JoinRequests,
     i18n: Arc<I18n>,
+    join_queue: JoinQueue,
 ) -> HandlerResult {
     // Si el grupo no está permitido, no continuamos
     if !config.groups_config.is_group_allowed(msg.chat.id) {
         return on_group_not_allowed(bot, &config.groups_config, msg, i18n).await;
     }
 
+    join_queue.push(msg);
+    Ok(())
+}
+
+/// Procesa los avisos de unión de un grupo sacados juntos de la cola, respondiendo al último
+async fn process_joins(join_queue: &JoinQueue, batch: Vec<Message>) -> HandlerResult {
+    let JoinQueue {
+        bot,
+        me,
+        config,
+        join_requests,
+        i18n,
+        events,
+        jobs,
+        ..
+    } = join_queue.clone();
+    let Some(msg) = batch.last().cloned() else {
+        return Ok(());
+    };
+
     let chat_cfg = config.groups_config.get(msg.chat.id);
 
-    for user in users {
-        if user.is_bot {
-            continue;
+    // Los mensajes "X se unió" se borran después de responderles con la bienvenida
+    let delete_service_messages = || {
+        if chat_cfg.auto_delete.service_messages {
+            for service_msg in &batch {
+                jobs.delete_message(service_msg.chat.id, service_msg.id, Duration::ZERO);
+            }
         }
+    };
 
-        let join_requests = join_requests.clone();
+    // Ignorar bots; el resto de usuarios se procesa como un solo lote
+    let users: Vec<User> = batch
+        .iter()
+        .flat_map(|msg| msg.new_chat_members().unwrap_or_default())
+        .filter(|user| !user.is_bot)
+        .cloned()
+        .collect();
+    if users.is_empty() {
+        delete_service_messages();
+        return Ok(());
+    }
 
-        // Detectar idioma basado en configuración o detección automática
-        let lang = chat_cfg
-            .language
-            .as_ref()
-            .map(|s| s.as_str())
-            .unwrap_or_else(|| i18n.detect_language(Some(&user)));
+    // Detectar idioma basado en configuración o en el primer usuario del lote
+    let lang = chat_cfg
+        .language
+        .as_deref()
+        .unwrap_or_else(|| i18n.detect_language(users.first()));
 
-        let translation = i18n.get(lang);
-        let messages = MessagesText::from_translation(translation);
+    let translation = i18n.get(lang);
+    let messages = chat_cfg.messages.resolve(translation);
+    let template = &messages.new_user_template;
 
-        // Crear mensaje de bienvenida traducido
-        let chat_name = escape(if let Some(ref title) = chat_cfg.chat_name {
-            title
+    // Preparar un botón de verificación por usuario, con una fila de botones para administradores debajo
+    let mut tokens = Vec::with_capacity(users.len());
+    let mut verify_urls = Vec::with_capacity(users.len());
+    let mut user_rows = Vec::with_capacity(users.len());
+    for user in &users {
+        let label = if users.len() > 1 {
+            format!("{} · {}", translation.verify_button, user.first_name)
         } else {
-            msg.chat.title().unwrap_or_default()
-        });
-
-        let welcome_msg = messages.create_welcome_msg(&user, &chat_name);
+            translation.verify_button.clone()
+        };
 
-        // Restringir permisos inicialmente (no enviar mensajes, etc.)
-        bot.restrict_chat_member(msg.chat.id, user.id, ChatPermissions::empty())
-            .await?;
-
-        // Botón de verificación World ID
-        let verify_button = InlineKeyboardButton::url(
-            &translation.verify_button,
+        // En modo privado el botón solo abre el chat con el bot
+        let token = JoinRequest::generate_token();
+        let url = if chat_cfg.verify_in_private {
+            let mut url = me.tme_url();
+            url.query_pairs_mut()
+                .append_pair("start", &format!("verify_{token}"));
+            url
+        } else {
             config
                 .app_url
-                .join(&format!("verify/{}/{}", msg.chat.id, user.id))?,
+                .join(&format!("verify/{}/{}", msg.chat.id, user.id))?
+        };
+
+        let mut row = vec![InlineKeyboardButton::url(label, url.clone())];
+        if chat_cfg.max_extensions > 0 {
+            row.push(WelcomeAction::resend_button(translation, user.id));
+        }
+
+        tokens.push(token);
+        verify_urls.push(url);
+        user_rows.push((user.id, vec![row, WelcomeAction::buttons(translation, user.id)]));
+    }
+
+    // Crear mensaje de bienvenida traducido (uno para todo el lote), antes de restringir a nadie
+    let member_count = if template.uses("member_count") {
+        send_with_retry(bot.get_chat_member_count(msg.chat.id))
+            .await
+            .map_err(|err| log::warn!("No se pudo obtener el número de miembros: {}", err))
+            .ok()
+    } else {
+        None
+    };
+    let mut welcome = WelcomeContext {
+        users: users.iter().cloned().zip(verify_urls).collect(),
+        chat_name: chat_cfg
+            .chat_name
+            .clone()
+            .unwrap_or_else(|| msg.chat.title().unwrap_or_default().to_string()),
+        timeout: chat_cfg.ban_after,
+        time_left: chat_cfg.ban_after,
+        member_count,
+        rules_url: chat_cfg.rules_url.clone(),
+    };
+    let welcome_msg = template.render(&welcome)?;
+
+    // Registrar las solicitudes de unión pendientes antes de nada, para anotar en ellas los errores
+    for (user, token) in users.iter().zip(tokens) {
+        // Los textos por privado van en el idioma de cada usuario, no en el del mensaje del lote
+        let user_lang = chat_cfg
+            .language
+            .as_deref()
+            .unwrap_or_else(|| i18n.detect_language(Some(user)));
+        let user_messages = chat_cfg.messages.resolve(i18n.get(user_lang));
+
+        events.joined(msg.chat.id, user, user_lang);
+        join_requests.insert(
+            (msg.chat.id, user.id),
+            JoinRequest::new(user.clone(), user_lang, user_messages, token, chat_cfg.ban_after),
         );
+    }
 
-        // Enviar mensaje de bienvenida con botón
-        let msg_id = bot
-            .send_message(msg.chat.id, welcome_msg)
-            .reply_to_message_id(msg.id)
-            .parse_mode(teloxide::types::ParseMode::Html)
-            .reply_markup(InlineKeyboardMarkup::new([vec![verify_button]]))
-            .await?
-            .id;
+    // Restringir permisos hasta que cada usuario se verifique. Si falla, el usuario sigue
+    // pendiente igualmente y sus mensajes se borran hasta que se verifique
+    let mut restricted = Vec::with_capacity(users.len());
+    for user in &users {
+        let restriction = bot.restrict_chat_member(msg.chat.id, user.id, ChatPermissions::empty());
+        match send_with_retry(restriction).await {
+            Ok(_) => {
+                events.restricted(msg.chat.id, user.id);
+                restricted.push(user.id);
+            },
+            Err(err) => record_failure(&join_requests, (msg.chat.id, user.id), "restrict", &err),
+        }
+    }
 
-        // Registrar la solicitud de unión pendiente
-        join_requests.insert((msg.chat.id, user.id), JoinRequest::new(msg_id));
+    // Enviar un único mensaje de bienvenida con los botones
+    let sent = send_welcome(
+        &bot,
+        &msg,
+        chat_cfg.welcome_media.as_ref(),
+        welcome_msg,
+        template.format().parse_mode(),
+        welcome_markup(&user_rows, |_| true),
+    )
+    .await;
+    let (msg_id, plain) = match sent {
+        Ok(sent) => (sent.id, false),
+        Err(err) => {
+            // El formato, el adjunto o un pie demasiado largo harían fallar siempre el envío;
+            // antes de dejar pasar al lote se prueba una vez con el texto de la traducción
+            log::warn!("No se pudo enviar la bienvenida, se reintenta sin formato: {}", err);
+            let markup = welcome_markup(&user_rows, |_| true);
+            match send_plain_welcome(&bot, &msg, translation, &welcome, markup).await {
+                Some(sent) => (sent.id, true),
+                None => {
+                    release_batch(
+                        &bot,
+                        msg.chat.id,
+                        &users,
+                        &restricted,
+                        &join_requests,
+                        &events,
+                        &err,
+                    )
+                    .await;
+                    return Err(err.into());
+                },
+            }
+        },
+    };
+    delete_service_messages();
+
+    // Todas las solicitudes del lote apuntan al mismo mensaje
+    for user in &users {
+        if let Some(mut join_req) = join_requests.get_mut(&(msg.chat.id, user.id)) {
+            join_req.msg_id = Some(msg_id);
+        }
+    }
 
-        // Lanzar tarea asíncrona para verificar si el usuario se valida
+    // Actualizar la cuenta atrás mientras queden usuarios pendientes en el mensaje (no en el
+    // de respaldo, que no sigue la plantilla del grupo)
+    let countdown_interval = Some(chat_cfg.countdown_interval)
+        .filter(|interval| !interval.is_zero())
+        .filter(|_| !plain)
+        .filter(|_| template.uses("time_left"));
+    if let Some(countdown_interval) = countdown_interval {
         tokio::spawn({
             let bot = bot.clone();
+            let template = template.clone();
+            let has_media = chat_cfg.welcome_media.is_some();
             let join_requests = join_requests.clone();
-            let user = user.clone();
-            let translation = translation.clone();
-            let ban_after = chat_cfg.ban_after;
+            let chat_id = msg.chat.id;
 
             async move {
-                // Esperar el tiempo configurado antes de expulsar
-                sleep(ban_after).await;
-
-                if let Some((_, data)) = join_requests.remove(&(msg.chat.id, user.id)) {
-                    if !data.is_verified {
-                        // 1️⃣ Expulsar al usuario del grupo
-                        if let Err(err) = bot.kick_chat_member(msg.chat.id, user.id).await {
-                            log::error!("Error al expulsar a {}: {}", user.full_name(), err);
-                        } else {
-                            log::info!("Usuario {} expulsado por no verificarse", user.full_name());
-                        }
-
-                        // 2️⃣ Eliminar el mensaje de verificación del grupo
-                        if let Some(msg_id) = data.msg_id {
-                            if let Err(err) = bot.delete_message(msg.chat.id, msg_id).await {
-                                log::warn!("No se pudo eliminar el mensaje de verificación: {}", err);
-                            }
-                        }
-
-                        // 3️⃣ Intentar enviar mensaje privado
-                        if let Err(err) = bot
-                            .send_message(user.id, &translation.user_doesnt_match_error)
+                let mut ticker = interval(countdown_interval);
+                ticker.tick().await;
+
+                loop {
+                    ticker.tick().await;
+
+                    let pending: Vec<(UserId, Instant)> = join_requests
+                        .iter()
+                        .filter(|req| {
+                            req.key().0 == chat_id && !req.is_verified && req.msg_id == Some(msg_id)
+                        })
+                        .map(|req| (req.key().1, req.expires_at))
+                        .collect();
+
+                    let Some(deadline) = pending.iter().map(|(_, expires_at)| *expires_at).min() else {
+                        break;
+                    };
+                    welcome.time_left = deadline.saturating_duration_since(Instant::now());
+                    if welcome.time_left.is_zero() {
+                        break;
+                    }
+
+                    let text = match template.render(&welcome) {
+                        Ok(text) => text,
+                        Err(err) => {
+                            log::error!("No se pudo generar el mensaje de bienvenida: {}", err);
+                            break;
+                        },
+                    };
+
+                    // Se reenvían solo los botones de los usuarios que siguen pendientes
+                    let markup = welcome_markup(&user_rows, |user_id| {
+                        pending.iter().any(|(pending_id, _)| *pending_id == user_id)
+                    });
+
+                    // Con un adjunto, el texto es su pie
+                    let parse_mode = template.format().parse_mode();
+                    let edited = if has_media {
+                        bot.edit_message_caption(chat_id, msg_id)
+                            .caption(text)
+                            .parse_mode(parse_mode)
+                            .reply_markup(markup)
                             .await
-                        {
-                            log::warn!(
-                                "No se pudo enviar mensaje privado a {}: {}",
-                                user.full_name(),
-                                err
-                            );
-                        }
+                    } else {
+                        bot.edit_message_text(chat_id, msg_id, text)
+                            .parse_mode(parse_mode)
+                            .reply_markup(markup)
+                            .await
+                    };
+
+                    if let Err(err) = edited {
+                        log::debug!("No se pudo actualizar la cuenta atrás: {}", err);
                     }
                 }
             }
         });
     }
 
+    // Lanzar una única tarea asíncrona que limpia el lote completo
+    tokio::spawn({
+        let bot = bot.clone();
+        let join_requests = join_requests.clone();
+
+        async move {
+            let mut pending = users;
+
+            // Esperar hasta el plazo más cercano del lote; los administradores pueden extenderlo
+            while let Some(deadline) = pending
+                .iter()
+                .filter_map(|user| {
+                    join_requests
+                        .get(&(msg.chat.id, user.id))
+                        .map(|req| req.expires_at)
+                })
+                .min()
+            {
+                sleep_until(deadline.into()).await;
+
+                let mut expired = Vec::new();
+                pending.retain(|user| {
+                    let key = (msg.chat.id, user.id);
+                    let state = join_requests
+                        .get(&key)
+                        .map(|req| (req.is_verified, req.is_expired()));
+
+                    match state {
+                        None => false,
+                        Some((true, _)) => {
+                            join_requests.remove(&key);
+                            false
+                        },
+                        Some((false, false)) => true,
+                        Some((false, true)) => {
+                            expired.push(user.clone());
+                            false
+                        },
+                    }
+                });
+
+                for user in expired {
+                    if let Err(err) =
+                        reject(&bot, msg.chat.id, user.id, &join_requests, &events, Actor::Bot)
+                            .await
+                    {
+                        log::error!("Error al expulsar a {}: {}", user.full_name(), err);
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Anota un error de Telegram en la solicitud del usuario, si sigue existiendo
+fn record_failure(
+    join_requests: &JoinRequests,
+    key: (ChatId, UserId),
+    step: &str,
+    err: impl fmt::Display,
+) {
+    match join_requests.get_mut(&key) {
+        Some(mut join_req) => join_req.record_failure(step, err),
+        None => log::warn!("Error de Telegram ({}) para {} en {}: {}", step, key.1, key.0, err),
+    }
+}
+
+/// Sin mensaje de bienvenida nadie del lote puede verificarse: se les quita la restricción
+/// y se descartan sus solicitudes, avisando a los administradores con los errores anotados
+async fn release_batch(
+    bot: &Bot,
+    chat_id: ChatId,
+    users: &[User],
+    restricted: &[UserId],
+    join_requests: &JoinRequests,
+    events: &Events,
+    err: &RequestError,
+) {
+    let permissions = match send_with_retry(bot.get_chat(chat_id)).await {
+        Ok(chat) => chat.permissions(),
+        Err(err) => {
+            log::error!("No se pudieron obtener los permisos del grupo {}: {}", chat_id, err);
+            None
+        },
+    };
+
+    for user in users {
+        let key = (chat_id, user.id);
+        record_failure(join_requests, key, "send welcome", err);
+
+        match &permissions {
+            _ if !restricted.contains(&user.id) => {},
+            Some(permissions) => {
+                let lift = bot.restrict_chat_member(chat_id, user.id, *permissions);
+                if let Err(err) = send_with_retry(lift).await {
+                    record_failure(join_requests, key, "lift restriction", &err);
+                }
+            },
+            None => record_failure(join_requests, key, "lift restriction", "unknown group permissions"),
+        }
+
+        if let Some((_, join_req)) = join_requests.remove(&key) {
+            events.released(chat_id, &join_req.user, &join_req.failures);
+        }
+    }
+}
+
+/// Borra el mensaje de servicio "X salió del grupo" si el grupo lo pide
+pub async fn left_handler(msg: Message, config: Arc<AppConfig>, jobs: Jobs) -> HandlerResult {
+    if config.groups_config.get(msg.chat.id).auto_delete.service_messages {
+        jobs.delete_message(msg.chat.id, msg.id, Duration::ZERO);
+    }
+
+    Ok(())
+}
+
+/// Envía el mensaje de bienvenida como respuesta a `msg`: texto, o el adjunto del grupo con el
+/// texto como pie. Se reintenta como el resto de llamadas antes de dar el lote por perdido
+async fn send_welcome(
+    bot: &Bot,
+    msg: &Message,
+    media: Option<&WelcomeMedia>,
+    text: String,
+    parse_mode: ParseMode,
+    markup: InlineKeyboardMarkup,
+) -> Result<Message, RequestError> {
+    let Some(media) = media else {
+        return send_with_retry(
+            bot.send_message(msg.chat.id, text)
+                .reply_to_message_id(msg.id)
+                .parse_mode(parse_mode)
+                .reply_markup(markup),
+        )
+        .await;
+    };
+
+    let file = match &media.source {
+        MediaSource::FileId(file_id) => InputFile::file_id(file_id),
+        MediaSource::Url(url) => InputFile::url(url.clone()),
+        MediaSource::Path(path) => InputFile::file(path),
+    };
+
+    match media.kind {
+        MediaKind::Photo => {
+            send_with_retry(
+                bot.send_photo(msg.chat.id, file)
+                    .caption(text)
+                    .reply_to_message_id(msg.id)
+                    .parse_mode(parse_mode)
+                    .reply_markup(markup),
+            )
+            .await
+        },
+        MediaKind::Animation => {
+            send_with_retry(
+                bot.send_animation(msg.chat.id, file)
+                    .caption(text)
+                    .reply_to_message_id(msg.id)
+                    .parse_mode(parse_mode)
+                    .reply_markup(markup),
+            )
+            .await
+        },
+        MediaKind::Video => {
+            send_with_retry(
+                bot.send_video(msg.chat.id, file)
+                    .caption(text)
+                    .reply_to_message_id(msg.id)
+                    .parse_mode(parse_mode)
+                    .reply_markup(markup),
+            )
+            .await
+        },
+    }
+}
+
+/// Envía la bienvenida de la traducción como texto sin formato ni adjunto, para cuando Telegram
+/// rechaza la del grupo
+async fn send_plain_welcome(
+    bot: &Bot,
+    msg: &Message,
+    translation: &Translation,
+    welcome: &WelcomeContext,
+    markup: InlineKeyboardMarkup,
+) -> Option<Message> {
+    let template = WelcomeTemplate::new(&translation.new_user_template, TemplateFormat::default());
+    let text = template
+        .render_plain(welcome)
+        .map_err(|err| log::error!("No se pudo generar la bienvenida sin formato: {}", err))
+        .ok()?;
+
+    let request = bot
+        .send_message(msg.chat.id, text)
+        .reply_to_message_id(msg.id)
+        .reply_markup(markup);
+    send_with_retry(request)
+        .await
+        .map_err(|err| log::error!("No se pudo enviar la bienvenida sin formato: {}", err))
+        .ok()
+}
+
+/// Botones del mensaje de bienvenida de los usuarios que cumplen `include`
+fn welcome_markup(
+    user_rows: &[(UserId, Vec<Vec<InlineKeyboardButton>>)],
+    include: impl Fn(UserId) -> bool,
+) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(
+        user_rows
+            .iter()
+            .filter(|(user_id, _)| include(*user_id))
+            .flat_map(|(_, rows)| rows.clone()),
+    )
+}
+
+/// Expulsa a un usuario pendiente, le avisa por privado y limpia su solicitud
+pub async fn reject(
+    bot: &Bot,
+    chat_id: ChatId,
+    user_id: UserId,
+    join_requests: &JoinRequests,
+    events: &Events,
+    actor: Actor,
+) -> HandlerResult {
+    let key = (chat_id, user_id);
+    let data = join_requests
+        .get(&key)
+        .map(|req| req.clone())
+        .ok_or("Can't find the join request")?;
+
+    // 1️⃣ Expulsar al usuario del grupo. Si falla, la solicitud sigue pendiente con el error anotado
+    if let Err(err) = send_with_retry(bot.kick_chat_member(chat_id, user_id)).await {
+        record_failure(join_requests, key, "kick", &err);
+        return Err(err.into());
+    }
+
+    // En los supergrupos expulsar es banear; se levanta el baneo para que pueda volver a unirse
+    let unban = bot.unban_chat_member(chat_id, user_id).only_if_banned(true);
+    if let Err(err) = send_with_retry(unban).await {
+        log::warn!("No se pudo levantar el baneo de {}: {}", data.user.full_name(), err);
+    }
+
+    join_requests.remove(&key);
+    events.removed(chat_id, &data.user, &data.lang, actor);
+    events.kicked(chat_id, user_id, &data.lang, actor);
+    log::info!("Usuario {} expulsado por no verificarse", data.user.full_name());
+
+    // 2️⃣ Intentar enviar mensaje privado
+    if let Err(err) = bot
+        .send_message(user_id, &data.messages.user_doesnt_match_error)
+        .await
+    {
+        log::warn!(
+            "No se pudo enviar mensaje privado a {}: {}",
+            data.user.full_name(),
+            err
+        );
+    }
+
+    // 3️⃣ Eliminar el mensaje de verificación si ya no quedan usuarios pendientes en él
+    if let Some(msg_id) = data.msg_id {
+        if let Err(err) =
+            delete_welcome_if_done(bot, join_requests, events, (chat_id, user_id), msg_id).await
+        {
+            log::warn!("No se pudo eliminar el mensaje de verificación: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Banea al usuario y, si seguía pendiente, cierra su solicitud como al expulsarlo.
+/// Si el baneo falla, la solicitud sigue pendiente con el error anotado
+pub async fn ban(
+    bot: &Bot,
+    chat_id: ChatId,
+    user_id: UserId,
+    join_requests: &JoinRequests,
+    events: &Events,
+    actor: Actor,
+) -> HandlerResult {
+    let key = (chat_id, user_id);
+    if let Err(err) = send_with_retry(bot.ban_chat_member(chat_id, user_id)).await {
+        record_failure(join_requests, key, "ban", &err);
+        return Err(err.into());
+    }
+
+    let Some((_, data)) = join_requests.remove(&key) else {
+        events.banned(chat_id, user_id, None, actor);
+        return Ok(());
+    };
+    let pending = (!data.is_verified).then_some((&data.user, data.lang.as_str()));
+    events.banned(chat_id, user_id, pending, actor);
+
+    if let Some(msg_id) = data.msg_id {
+        if let Err(err) = delete_welcome_if_done(bot, join_requests, events, key, msg_id).await {
+            log::warn!("No se pudo eliminar el mensaje de verificación: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Elimina el mensaje de bienvenida (compartido por el lote) cuando ya no quedan usuarios pendientes
+async fn delete_welcome_if_done(
+    bot: &Bot,
+    join_requests: &JoinRequests,
+    events: &Events,
+    (chat_id, user_id): (ChatId, UserId),
+    msg_id: MessageId,
+) -> Result<(), RequestError> {
+    let shared = join_requests.iter().any(|req| {
+        req.key().0 == chat_id && !req.is_verified && req.msg_id == Some(msg_id)
+    });
+
+    if !shared {
+        send_with_retry(bot.delete_message(chat_id, msg_id)).await?;
+        events.message_deleted(chat_id, user_id, "welcome message");
+    }
+
+    Ok(())
+}
+
+/// Continúa la verificación por privado a partir de un enlace `?start=verify_<token>`
+pub async fn private_verification(
+    bot: Bot,
+    msg: Message,
+    token: &str,
+    config: Arc<AppConfig>,
+    join_requests: JoinRequests,
+    i18n: Arc<I18n>,
+) -> HandlerResult {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+
+    let found = join_requests
+        .iter()
+        .find(|req| req.token == token)
+        .map(|req| (*req.key(), req.lang.clone(), req.messages.clone(), req.is_verified));
+
+    // El token no existe: la solicitud expiró o el enlace es incorrecto
+    let Some(((chat_id, user_id), lang, messages, is_verified)) = found else {
+        let translation = i18n.get(i18n.detect_language(Some(user)));
+        bot.send_message(msg.chat.id, &translation.verify_private_invalid)
+            .await?;
+        return Ok(());
+    };
+
+    let translation = i18n.get(&lang);
+
+    if user.id != user_id {
+        bot.send_message(msg.chat.id, &messages.user_doesnt_match_error)
+            .await?;
+        return Ok(());
+    }
+
+    if is_verified {
+        bot.send_message(msg.chat.id, &messages.successfully_verified)
+            .await?;
+        return Ok(());
+    }
+
+    let chat_cfg = config.groups_config.get(chat_id);
+    let chat_name = match chat_cfg.chat_name {
+        Some(ref title) => title.clone(),
+        None => bot
+            .get_chat(chat_id)
+            .await?
+            .title()
+            .unwrap_or_default()
+            .to_string(),
+    };
+
+    // En privado podemos abrir la página como Mini App sin salir de Telegram
+    let url = config
+        .app_url
+        .join(&format!("verify/{}/{}", chat_id, user_id))?;
+    let verify_button = if chat_cfg.use_web_app {
+        InlineKeyboardButton::web_app(&translation.verify_button, WebAppInfo { url })
+    } else {
+        InlineKeyboardButton::url(&translation.verify_button, url)
+    };
+
+    let prompt = bot
+        .send_message(
+            msg.chat.id,
+            translation
+                .verify_private_prompt
+                .replace("{CHATNAME}", &escape(&chat_name)),
+        )
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .reply_markup(InlineKeyboardMarkup::new([vec![verify_button]]));
+    let dm_msg_id = send_with_retry(prompt).await?.id;
+
+    // Guardar el mensaje privado para actualizar su estado al verificarse
+    if let Some(mut join_req) = join_requests.get_mut(&(chat_id, user_id)) {
+        join_req.dm_msg_id = Some(dm_msg_id);
+    }
+
     Ok(())
 }
 
@@ -132,25 +712,64 @@ pub async fn on_verified(
     chat_id: ChatId,
     user_id: UserId,
     join_requests: JoinRequests,
+    events: Events,
+    actor: Actor,
 ) -> HandlerResult {
-    let mut join_req = join_requests
-        .get_mut(&(chat_id, user_id))
-        .ok_or("Can't find the message id in group dialogue")?;
+    if !join_requests.contains_key(&(chat_id, user_id)) {
+        return Err("Can't find the message id in group dialogue".into());
+    }
 
-    let Some(permissions) = bot.get_chat(chat_id).await?.permissions() else {
+    let Some(permissions) = send_with_retry(bot.get_chat(chat_id)).await?.permissions() else {
         return Err("Can't get the group permissions".into());
     };
 
-    // Marcar como verificado
-    join_req.is_verified = true;
+    // Restaurar permisos originales. Si falla, la solicitud sigue pendiente y se puede reintentar
+    let restore = bot.restrict_chat_member(chat_id, user_id, permissions);
+    if let Err(err) = send_with_retry(restore).await {
+        record_failure(&join_requests, (chat_id, user_id), "lift restriction", &err);
+        return Err(err.into());
+    }
+
+    // Marcar como verificado, ya con los permisos restaurados
+    let (msg_id, dm_msg_id, messages, user, credential_type) = {
+        let mut join_req = join_requests
+            .get_mut(&(chat_id, user_id))
+            .ok_or("Can't find the message id in group dialogue")?;
+
+        // Otra aprobación simultánea ya terminó
+        if join_req.is_verified {
+            return Ok(());
+        }
+
+        join_req.is_verified = true;
+        (
+            join_req.msg_id.take(),
+            join_req.dm_msg_id.take(),
+            join_req.messages.clone(),
+            join_req.user.clone(),
+            join_req.credential_type.clone(),
+        )
+    };
 
-    // Restaurar permisos originales
-    bot.restrict_chat_member(chat_id, user_id, permissions)
-        .await?;
+    events.approved(chat_id, &user, actor, credential_type.as_deref());
 
-    // Eliminar mensaje de bienvenida
-    if let Some(msg_id) = join_req.msg_id.take() {
-        bot.delete_message(chat_id, msg_id).await?;
+    // Eliminar mensaje de bienvenida si ya no quedan usuarios pendientes en él. El usuario ya
+    // está verificado, así que un error aquí solo se anota
+    if let Some(msg_id) = msg_id {
+        let key = (chat_id, user_id);
+        if let Err(err) = delete_welcome_if_done(&bot, &join_requests, &events, key, msg_id).await {
+            record_failure(&join_requests, key, "delete welcome", &err);
+        }
+    }
+
+    // Actualizar el estado en el chat privado, si la verificación empezó ahí
+    if let Some(dm_msg_id) = dm_msg_id {
+        if let Err(err) = bot
+            .edit_message_text(user_id, dm_msg_id, &messages.successfully_verified)
+            .await
+        {
+            log::warn!("No se pudo actualizar el mensaje privado: {}", err);
+        }
     }
 
     Ok(())
@@ -0,0 +1,147 @@
+use dashmap::DashMap;
+use std::{sync::Arc, time::Duration};
+use teloxide::types::{ChatId, Me, Message};
+use tokio::{
+	sync::mpsc::{self, UnboundedReceiver, UnboundedSender, WeakUnboundedSender},
+	time::timeout,
+};
+
+use crate::{
+	bot::{Bot, JoinRequests},
+	config::AppConfig,
+	events::Events,
+	i18n::I18n,
+	jobs::Jobs,
+};
+
+use super::process_joins;
+
+/// Tiempo sin uniones tras el que se cierra la cola de un grupo
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Cola de avisos de unión por grupo. Cada grupo tiene su propia tarea, que procesa las uniones
+/// en orden; las que se acumulan mientras tanto (una avalancha, o envíos frenados por los
+/// límites) se saludan juntas en un mismo mensaje
+#[derive(Clone)]
+pub struct JoinQueue {
+	pub bot: Bot,
+	pub me: Me,
+	pub config: Arc<AppConfig>,
+	pub join_requests: JoinRequests,
+	pub i18n: Arc<I18n>,
+	pub events: Events,
+	pub jobs: Jobs,
+	chats: Arc<DashMap<ChatId, UnboundedSender<Message>>>,
+}
+
+impl JoinQueue {
+	pub fn new(
+		bot: Bot,
+		me: Me,
+		config: Arc<AppConfig>,
+		join_requests: JoinRequests,
+		i18n: Arc<I18n>,
+		events: Events,
+		jobs: Jobs,
+	) -> Self {
+		Self {
+			bot,
+			me,
+			config,
+			join_requests,
+			i18n,
+			events,
+			jobs,
+			chats: Arc::new(DashMap::new()),
+		}
+	}
+
+	/// Añade el aviso a la cola de su grupo, lanzando la tarea del grupo si no estaba activa
+	pub fn push(&self, msg: Message) {
+		let chat_id = msg.chat.id;
+		let mut sender = self
+			.chats
+			.entry(chat_id)
+			.or_insert_with(|| self.spawn(chat_id));
+
+		// La tarea del grupo pudo terminar con un panic; se reemplaza por una nueva
+		if let Err(mpsc::error::SendError(msg)) = sender.send(msg) {
+			*sender = self.spawn(chat_id);
+			let _ = sender.send(msg);
+		}
+	}
+
+	fn spawn(&self, chat_id: ChatId) -> UnboundedSender<Message> {
+		let (tx, rx) = mpsc::unbounded_channel();
+		tokio::spawn(self.clone().run(chat_id, tx.downgrade(), rx));
+
+		tx
+	}
+
+	async fn run(
+		self,
+		chat_id: ChatId,
+		own: WeakUnboundedSender<Message>,
+		mut rx: UnboundedReceiver<Message>,
+	) {
+		let batch_size = self.config.throttle.join_batch_size;
+		let mut next = None;
+
+		loop {
+			let first = match next.take() {
+				Some(msg) => msg,
+				None => match timeout(IDLE_TIMEOUT, rx.recv()).await {
+					Ok(Some(msg)) => msg,
+					Ok(None) => break,
+					Err(_) => match self.close(chat_id, &own, &mut rx) {
+						Some(msg) => msg,
+						None => break,
+					},
+				},
+			};
+
+			// Sumar los avisos que ya esperaban mientras quepan en una misma bienvenida
+			let mut users = join_count(&first);
+			let mut batch = vec![first];
+			while let Ok(msg) = rx.try_recv() {
+				users += join_count(&msg);
+				if users > batch_size {
+					next = Some(msg);
+					break;
+				}
+				batch.push(msg);
+			}
+
+			if batch.len() > 1 {
+				log::info!(
+					"Processing {} join messages together in {chat_id}",
+					batch.len()
+				);
+			}
+			if let Err(err) = process_joins(&self, batch).await {
+				log::error!("Failed to process joins in {chat_id}: {err}");
+			}
+		}
+	}
+
+	/// Quita la cola del grupo si sigue vacía y es la de esta tarea. Se comprueba con el grupo
+	/// bloqueado, así que un `push` llega aquí (y se devuelve) o ya crea una tarea nueva
+	fn close(
+		&self,
+		chat_id: ChatId,
+		own: &WeakUnboundedSender<Message>,
+		rx: &mut UnboundedReceiver<Message>,
+	) -> Option<Message> {
+		let mut received = None;
+		self.chats.remove_if(&chat_id, |_, tx| {
+			received = rx.try_recv().ok();
+			received.is_none() && own.upgrade().is_some_and(|own| own.same_channel(tx))
+		});
+
+		received
+	}
+}
+
+fn join_count(msg: &Message) -> usize {
+	msg.new_chat_members().map_or(0, <[_]>::len)
+}
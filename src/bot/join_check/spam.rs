@@ -0,0 +1,66 @@
+use std::sync::Arc;
+use teloxide::{prelude::*, types::Message};
+
+use crate::{
+    audit::Actor,
+    bot::{Bot, HandlerResult, JoinRequests},
+    config::AppConfig,
+    events::Events,
+};
+
+use super::ban;
+
+/// Determina si el mensaje es de un usuario que todavía no se verificó en el grupo
+pub fn is_from_pending_user(msg: Message, join_requests: JoinRequests) -> bool {
+    msg.from().is_some_and(|user| {
+        join_requests
+            .get(&(msg.chat.id, user.id))
+            .is_some_and(|req| !req.is_verified)
+    })
+}
+
+/// Borra los mensajes de usuarios pendientes, por si la restricción falló o aún no se aplicó,
+/// y los banea al llegar a `ban_after_messages`
+pub async fn pending_message_handler(
+    bot: Bot,
+    msg: Message,
+    config: Arc<AppConfig>,
+    join_requests: JoinRequests,
+    events: Events,
+) -> HandlerResult {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    let key = (msg.chat.id, user.id);
+
+    if let Err(err) = bot.delete_message(msg.chat.id, msg.id).await {
+        log::warn!("No se pudo borrar el mensaje de {} sin verificar: {}", user.full_name(), err);
+    }
+
+    let Some(attempts) = join_requests.get_mut(&key).map(|mut req| {
+        req.blocked_messages += 1;
+        req.blocked_messages
+    }) else {
+        return Ok(());
+    };
+
+    log::info!(
+        "Mensaje de {} borrado en {}: aún no se verificó ({} intentos)",
+        user.full_name(),
+        msg.chat.id,
+        attempts
+    );
+    events.pending_message_deleted(msg.chat.id, user.id, attempts);
+
+    let ban_after = config.groups_config.get(msg.chat.id).ban_after_messages;
+    if ban_after.is_none_or(|ban_after| attempts < ban_after) {
+        return Ok(());
+    }
+
+    // Límite alcanzado: banear y cerrar la solicitud. Si el baneo falla,
+    // el usuario sigue pendiente y sus mensajes se siguen borrando
+    ban(&bot, msg.chat.id, user.id, &join_requests, &events, Actor::Bot).await?;
+    log::info!("Usuario {} baneado por escribir sin verificarse", user.full_name());
+
+    Ok(())
+}
@@ -1,60 +1,172 @@
 use dashmap::DashMap;
-use std::sync::Arc;
+use rand::{distributions::Alphanumeric, Rng};
+use std::{
+	fmt,
+	sync::Arc,
+	time::{Duration, Instant},
+};
 use teloxide::{
+	adaptors::Throttle,
 	dispatching::{MessageFilterExt, UpdateFilterExt},
 	prelude::{dptree, Dispatcher},
 	requests::Requester,
-	types::{ChatId, Message, MessageId, Update, UserId},
+	types::{ChatId, Me, Message, MessageId, Update, User, UserId},
 	utils::command::BotCommands,
-	Bot,
+	RequestError,
+};
+
+use crate::{
+	bot::commands::Command,
+	config::{AppConfig, MessagesText},
+	events::Events,
+	i18n::I18n,
+	jobs::Jobs,
 };
 
-use crate::{bot::commands::Command, config::AppConfig, i18n::I18n}; // MODIFICAR ESTA LÍNEA
+pub use join_check::{ban, on_verified, reject};
 
-pub use join_check::on_verified;
+use join_check::JoinQueue;
 
+mod callbacks;
 mod commands;
 mod join_check;
+mod retry;
 
 type HandlerResult = Result<(), HandlerError>;
 pub type JoinRequests = Arc<DashMap<(ChatId, UserId), JoinRequest>>;
 type HandlerError = Box<dyn std::error::Error + Send + Sync>;
 
+/// Bot cuyos mensajes esperan su turno para no superar los límites de Telegram
+pub type Bot = Throttle<teloxide::Bot>;
+
+/// Crea el bot y lanza la tarea que reparte sus envíos según `throttle`
+pub fn build(config: &AppConfig) -> Bot {
+	Throttle::new_spawn(teloxide::Bot::new(&config.bot_token), config.throttle.limits())
+}
+
 #[derive(Debug, Clone)]
 pub struct JoinRequest {
+	pub user: User,
+	pub lang: String,
+	/// Idioma de Telegram del usuario (`language_code`) sin resolver; `None` si no lo comparte
+	pub language_code: Option<String>,
+	/// Textos del grupo en el idioma del usuario, fijados al unirse
+	pub messages: MessagesText,
+	pub token: String,
 	pub is_verified: bool,
+	pub expires_at: Instant,
+	/// Credencial de World ID usada, una vez validada la prueba
+	pub credential_type: Option<String>,
+	/// Veces que el propio usuario pidió un enlace nuevo y más tiempo
+	pub extensions: u32,
+	/// Mensajes que el usuario envió al grupo antes de verificarse, ya borrados
+	pub blocked_messages: u32,
+	/// Errores de Telegram al procesar la solicitud, como `<paso>: <error>`
+	pub failures: Vec<String>,
 	pub msg_id: Option<MessageId>,
+	pub dm_msg_id: Option<MessageId>,
 }
 
 impl JoinRequest {
-	pub fn new(msg_id: MessageId) -> Self {
+	pub fn new(
+		user: User,
+		lang: &str,
+		messages: MessagesText,
+		token: String,
+		ban_after: Duration,
+	) -> Self {
 		Self {
+			language_code: user.language_code.clone(),
+			user,
+			messages,
+			token,
+			dm_msg_id: None,
+			expires_at: Instant::now() + ban_after,
 			is_verified: false,
-			msg_id: Some(msg_id),
+			credential_type: None,
+			extensions: 0,
+			blocked_messages: 0,
+			failures: Vec::new(),
+			msg_id: None,
+			lang: lang.to_string(),
 		}
 	}
+
+	pub fn record_failure(&mut self, step: &str, err: impl fmt::Display) {
+		log::warn!("Failed to {step} for {} ({}): {err}", self.user.full_name(), self.user.id);
+		self.failures.push(format!("{step}: {err}"));
+	}
+
+	pub fn is_expired(&self) -> bool {
+		Instant::now() >= self.expires_at
+	}
+
+	/// Random token used in `t.me/<bot>?start=verify_<token>` deep links.
+	pub fn generate_token() -> String {
+		rand::thread_rng()
+			.sample_iter(&Alphanumeric)
+			.take(24)
+			.map(char::from)
+			.collect()
+	}
+}
+
+/// Administrador del grupo en Telegram o incluido en `admin_ids`
+pub async fn is_group_admin(
+	bot: &Bot,
+	config: &AppConfig,
+	chat_id: ChatId,
+	user_id: UserId,
+) -> Result<bool, RequestError> {
+	if config.groups_config.is_admin(chat_id, user_id) {
+		return Ok(true);
+	}
+
+	Ok(bot.get_chat_member(chat_id, user_id).await?.is_privileged())
 }
 
 pub async fn start(
 	bot: Bot,
+	me: Me,
 	config: AppConfig,
 	join_requests: JoinRequests,
 	i18n: Arc<I18n>, // AÑADIR ESTE PARÁMETRO
+	events: Events,
+	jobs: Jobs,
 ) {
 	log::info!("Starting World ID bot...");
 	bot.set_my_commands(Command::bot_commands())
 		.await
 		.expect("Failed to set commands");
-	
-	let handler = dptree::entry().branch(
-		Update::filter_message()
-			.branch(Message::filter_new_chat_members().endpoint(join_check::join_handler))
-			.branch(Message::filter_text().endpoint(commands::command_handler)),
+
+	// Las uniones se procesan por grupo y en orden, sin bloquear el resto de actualizaciones
+	let config = Arc::new(config);
+	let join_queue = JoinQueue::new(
+		bot.clone(),
+		me,
+		config.clone(),
+		join_requests.clone(),
+		i18n.clone(),
+		events.clone(),
+		jobs.clone(),
 	);
 	
+	let handler = dptree::entry()
+		.branch(
+			Update::filter_message()
+				.branch(Message::filter_new_chat_members().endpoint(join_check::join_handler))
+				.branch(Message::filter_left_chat_member().endpoint(join_check::left_handler))
+				.branch(
+					dptree::filter(join_check::is_from_pending_user)
+						.endpoint(join_check::pending_message_handler),
+				)
+				.branch(Message::filter_text().endpoint(commands::command_handler)),
+		)
+		.branch(Update::filter_callback_query().endpoint(callbacks::callback_handler));
+	
 	Dispatcher::builder(bot, handler)
 		.default_handler(|_| async {})
-		.dependencies(dptree::deps![Arc::new(config), join_requests, i18n]) // MODIFICAR ESTA LÍNEA
+		.dependencies(dptree::deps![config, join_requests, i18n, events, jobs, join_queue])
 		.enable_ctrlc_handler()
 		.build()
 		.dispatch()
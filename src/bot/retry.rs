@@ -0,0 +1,40 @@
+use std::time::Duration;
+use teloxide::{
+	requests::{Output, Request},
+	RequestError,
+};
+
+/// Intentos de cada petición, contando el primero
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Espera antes del primer reintento ante errores de red; se duplica en cada intento
+const BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Envía la petición y la reintenta si Telegram pide esperar (`RetryAfter`)
+/// o si falla la red. El resto de errores se devuelven de inmediato
+pub async fn send_with_retry<R>(request: R) -> Result<Output<R>, RequestError>
+where
+	R: Request<Err = RequestError>,
+{
+	let mut attempt = 1;
+
+	loop {
+		let err = match request.send_ref().await {
+			Ok(output) => return Ok(output),
+			Err(err) => err,
+		};
+
+		let delay = match &err {
+			RequestError::RetryAfter(delay) => *delay,
+			RequestError::Network(_) | RequestError::Io(_) => BASE_DELAY * 2u32.pow(attempt - 1),
+			_ => return Err(err),
+		};
+		if attempt >= MAX_ATTEMPTS {
+			return Err(err);
+		}
+
+		log::warn!("Telegram request failed ({err}), retrying in {delay:?} ({attempt}/{MAX_ATTEMPTS})");
+		tokio::time::sleep(delay).await;
+		attempt += 1;
+	}
+}
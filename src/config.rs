@@ -1,14 +1,19 @@
 use config::{Config, ConfigError, Environment, File};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
-use std::{collections::HashMap, time::Duration};
+use dashmap::DashMap;
+use std::{collections::HashMap, fmt, path::PathBuf, sync::Arc, time::Duration};
 use teloxide::{
-    types::{ChatId, User, UserId},
-    utils::html::escape,
+    adaptors::throttle::Limits,
+    types::{ChatId, UserId},
 };
 use url::Url;
 
-use crate::i18n::Translation; // Mantener esta línea
+use crate::{
+    i18n::Translation,
+    messages::{TemplateFormat, WelcomeTemplate},
+    webhooks::WebhookEvent,
+};
 
 /// Configuración principal de la aplicación
 #[derive(Debug, Clone, Deserialize)]
@@ -21,24 +26,232 @@ pub struct AppConfig {
     pub groups_config: GroupsConfig,
 
     pub posthog_token: Option<String>,
+
+    /// Destinos adicionales para los eventos de analítica
+    #[serde(default)]
+    pub analytics: Vec<AnalyticsSinkConfig>,
+
+    /// Webhooks salientes para todos los grupos
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// Clave para la API de administración (`Authorization: Bearer <clave>`).
+    /// La API queda desactivada si no se configura
+    pub admin_api_key: Option<String>,
+
+    /// Token para leer las métricas de Prometheus en /metrics (`Authorization: Bearer <token>`).
+    /// Sin él, /metrics no existe
+    pub metrics_token: Option<String>,
+
+    /// Origen del bundle de IDKit usado por la página de verificación
+    #[serde(default)]
+    pub idkit: IdKitConfig,
+
+    /// Persistencia y retención del registro de auditoría
+    #[serde(default)]
+    pub audit: AuditConfig,
+
+    /// Cada cuánto se comprueban los permisos del bot en los grupos ("0s": solo al iniciar)
+    #[serde(with = "humantime_serde", default = "AppConfig::default_permission_check_interval")]
+    pub permission_check_interval: Duration,
+
+    /// Límites de envío a Telegram y agrupación de uniones durante avalanchas
+    #[serde(default)]
+    pub throttle: ThrottleConfig,
 }
 
 impl AppConfig {
     /// Lee configuración desde archivos y variables de entorno
     pub fn try_read() -> Result<AppConfig, ConfigError> {
-        Config::builder()
+        let config: AppConfig = Config::builder()
             .add_source(File::with_name("config.toml").required(false))
             .add_source(File::with_name("config.dev.toml").required(false))
             .add_source(Environment::with_prefix("WLD_CAPTCHA"))
             .build()?
-            .try_deserialize()
+            .try_deserialize()?;
+
+        // Las plantillas con errores se detectan al iniciar y no con el primer usuario
+        config
+            .groups_config
+            .validate()
+            .map_err(ConfigError::Message)?;
+        for webhook in &config.webhooks {
+            webhook.validate().map_err(ConfigError::Message)?;
+        }
+
+        Ok(config)
+    }
+
+    fn default_permission_check_interval() -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+}
+
+/// Destino de analítica configurable (`[[analytics]]` con `type = "..."`)
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnalyticsSinkConfig {
+    Posthog {
+        token: String,
+    },
+    Jsonl {
+        path: PathBuf,
+    },
+    Webhook {
+        url: Url,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+// Sin el token de PostHog ni los valores de las cabeceras, que suelen llevar credenciales
+impl fmt::Debug for AnalyticsSinkConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Posthog { .. } => f.debug_struct("Posthog").finish_non_exhaustive(),
+            Self::Jsonl { path } => f.debug_struct("Jsonl").field("path", path).finish(),
+            Self::Webhook { url, headers } => f
+                .debug_struct("Webhook")
+                .field("url", url)
+                .field("headers", &headers.keys().collect::<Vec<_>>())
+                .finish(),
+        }
+    }
+}
+
+/// Webhook saliente firmado con `X-Signature-256: sha256=<hmac>`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookConfig {
+    pub url: Url,
+
+    /// Obligatoria, pero nunca se devuelve por la API de administración: al reenviar la
+    /// configuración sin ella se conserva la guardada para la misma `url`
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+
+    /// Eventos a enviar; todos si está vacío
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+}
+
+impl WebhookConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.secret {
+            Some(_) => Ok(()),
+            None => Err(format!("webhook {} has no secret", self.url)),
+        }
+    }
+}
+
+/// Bundle de IDKit: local (por defecto) o desde una URL externa
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdKitConfig {
+    /// Archivo servido desde `/static/idkit.js`
+    #[serde(default = "IdKitConfig::default_bundle_path")]
+    pub bundle_path: PathBuf,
+
+    /// URL externa con versión fija que reemplaza al bundle local (por ejemplo, un CDN propio).
+    /// Requiere `integrity`
+    pub url: Option<Url>,
+
+    /// Hash SRI esperado (`sha384-...`). Con un bundle local se comprueba al iniciar
+    pub integrity: Option<String>,
+}
+
+impl IdKitConfig {
+    fn default_bundle_path() -> PathBuf {
+        PathBuf::from("assets/idkit.js")
+    }
+}
+
+impl Default for IdKitConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            integrity: None,
+            bundle_path: Self::default_bundle_path(),
+        }
+    }
+}
+
+/// Registro de auditoría guardado como JSONL
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuditConfig {
+    /// Archivo del registro; con una ruta vacía se guarda solo en memoria
+    #[serde(default = "AuditConfig::default_path")]
+    pub path: Option<PathBuf>,
+
+    /// Antigüedad máxima de las entradas (ej. "90d")
+    #[serde(with = "humantime_serde", default = "AuditConfig::default_retention")]
+    pub retention: Option<Duration>,
+
+    /// Número máximo de entradas guardadas
+    pub max_entries: Option<usize>,
+}
+
+impl AuditConfig {
+    fn default_path() -> Option<PathBuf> {
+        Some(PathBuf::from("audit.jsonl"))
+    }
+
+    fn default_retention() -> Option<Duration> {
+        Some(Duration::from_secs(60 * 60 * 24 * 90))
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            path: Self::default_path(),
+            retention: Self::default_retention(),
+            max_entries: None,
+        }
+    }
+}
+
+/// Límites de mensajes enviados por el bot, aplicados antes de que Telegram responda con 429
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    /// Mensajes por segundo en un mismo chat
+    pub messages_per_sec_chat: u32,
+
+    /// Mensajes por minuto en un mismo grupo
+    pub messages_per_min_chat: u32,
+
+    /// Mensajes por minuto en un mismo canal
+    pub messages_per_min_channel: u32,
+
+    /// Mensajes por segundo en total
+    pub messages_per_sec_overall: u32,
+
+    /// Usuarios como máximo en una bienvenida formada por uniones que esperaban en la cola
+    pub join_batch_size: usize,
+}
+
+impl ThrottleConfig {
+    pub const fn limits(&self) -> Limits {
+        Limits {
+            messages_per_sec_chat: self.messages_per_sec_chat,
+            messages_per_min_chat: self.messages_per_min_chat,
+            messages_per_min_channel: self.messages_per_min_channel,
+            messages_per_sec_overall: self.messages_per_sec_overall,
+        }
     }
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        // Los límites de la documentación de Telegram
+        let limits = Limits::default();
 
-    /// Cliente opcional de PostHog para métricas
-    pub fn posthog(&self) -> Option<posthog_rs::Client> {
-        self.posthog_token
-            .as_ref()
-            .map(|token| posthog_rs::client(token.as_str()))
+        Self {
+            messages_per_sec_chat: limits.messages_per_sec_chat,
+            messages_per_min_chat: limits.messages_per_min_chat,
+            messages_per_min_channel: limits.messages_per_min_channel,
+            messages_per_sec_overall: limits.messages_per_sec_overall,
+            join_batch_size: 10,
+        }
     }
 }
 
@@ -58,6 +271,10 @@ pub struct GroupsConfig {
     #[serde_as(as = "HashMap<DisplayFromStr, _>")]
     #[serde(default)]
     group_settings: HashMap<i64, GroupSettings>,
+
+    /// Cambios hechos en tiempo de ejecución (API de administración), con prioridad sobre el archivo
+    #[serde(skip)]
+    overrides: Arc<DashMap<i64, GroupSettings>>,
 }
 
 impl GroupsConfig {
@@ -66,28 +283,184 @@ impl GroupsConfig {
         self.allowed_group_ids.is_empty() || self.allowed_group_ids.contains(&chat_id)
     }
 
+    /// Itera sobre los grupos configurados explícitamente
+    pub fn groups(&self) -> impl Iterator<Item = (ChatId, &GroupSettings)> {
+        self.group_settings
+            .iter()
+            .map(|(chat_id, settings)| (ChatId(*chat_id), settings))
+    }
+
+    /// Grupos con configuración propia, ya sea del archivo o de tiempo de ejecución
+    pub fn chat_ids(&self) -> Vec<ChatId> {
+        let mut chat_ids: Vec<ChatId> = self
+            .group_settings
+            .keys()
+            .copied()
+            .chain(self.overrides.iter().map(|entry| *entry.key()))
+            .map(ChatId)
+            .collect();
+
+        chat_ids.sort_by_key(|chat_id| chat_id.0);
+        chat_ids.dedup();
+        chat_ids
+    }
+
     /// Obtiene la configuración específica de un grupo
-    pub fn get(&self, chat_id: ChatId) -> &GroupSettings {
+    pub fn get(&self, chat_id: ChatId) -> GroupSettings {
+        if let Some(settings) = self.overrides.get(&chat_id.0) {
+            return settings.clone();
+        }
+
         self.group_settings
             .get(&chat_id.0)
             .unwrap_or(&self.fallback_group_settings)
+            .clone()
+    }
+
+    /// Determina si el usuario está en `admin_ids` del grupo
+    pub fn is_admin(&self, chat_id: ChatId, user_id: UserId) -> bool {
+        self.get(chat_id)
+            .admin_ids
+            .is_some_and(|admin_ids| admin_ids.contains(&user_id))
+    }
+
+    /// Determina si el usuario administra al menos un grupo (incluido el fallback)
+    pub fn is_admin_anywhere(&self, user_id: UserId) -> bool {
+        let is_admin = |settings: &GroupSettings| {
+            settings
+                .admin_ids
+                .as_ref()
+                .is_some_and(|admin_ids| admin_ids.contains(&user_id))
+        };
+
+        is_admin(&self.fallback_group_settings)
+            || self.group_settings.values().any(is_admin)
+            || self.overrides.iter().any(|entry| is_admin(entry.value()))
+    }
+
+    /// Comprueba las plantillas del fallback y de cada grupo configurado
+    pub fn validate(&self) -> Result<(), String> {
+        self.fallback_group_settings.validate()?;
+
+        for (chat_id, settings) in self.groups() {
+            settings
+                .validate()
+                .map_err(|e| format!("group_settings.{chat_id}: {e}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Reemplaza la configuración de un grupo hasta el próximo reinicio
+    pub fn set(&self, chat_id: ChatId, settings: GroupSettings) {
+        self.overrides.insert(chat_id.0, settings);
     }
 }
 
 /// Configuración de cada grupo
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct GroupSettings {
     pub chat_name: Option<String>,
+
+    /// Usuarios con acceso al panel de administración del grupo
     pub admin_ids: Option<Vec<UserId>>,
 
     /// Tiempo de espera antes de expulsar usuarios no verificados (ej. "5m")
     #[serde(with = "humantime_serde")]
     pub ban_after: Duration,
 
+    /// Textos propios del grupo; los que no se indiquen salen de la traducción
     #[serde(default)]
-    pub messages: MessagesText,
+    pub messages: GroupMessages,
+
+    /// Reglas del grupo, disponibles como `{{ rules_url }}` en el mensaje de bienvenida
+    pub rules_url: Option<Url>,
+
+    /// Foto, GIF o vídeo enviado con el mensaje de bienvenida como pie
+    pub welcome_media: Option<WelcomeMedia>,
+
+    /// Borrado automático de mensajes de servicio, comandos y avisos del bot
+    #[serde(default)]
+    pub auto_delete: AutoDeleteSettings,
 
     pub language: Option<String>,
+
+    /// Si está activo, el mensaje del grupo solo contiene un enlace
+    /// `t.me/<bot>?start=verify_<token>` y la verificación continúa por privado
+    #[serde(default)]
+    pub verify_in_private: bool,
+
+    /// Abre la verificación como Mini App de Telegram en el chat privado
+    /// (requiere `verify_in_private`, Telegram no permite botones `web_app` en grupos)
+    #[serde(default)]
+    pub use_web_app: bool,
+
+    /// Credenciales de World ID aceptadas en el grupo (`orb`, `phone`)
+    #[serde(default = "GroupSettings::default_credential_types")]
+    pub credential_types: Vec<String>,
+
+    /// Personalización de la página de verificación
+    #[serde(default)]
+    pub page: PageSettings,
+
+    /// Webhooks salientes solo para este grupo
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// Canal o grupo privado donde publicar los avisos de moderación
+    pub log_chat_id: Option<ChatId>,
+
+    /// Cada cuánto se actualiza la cuenta atrás `{TIMELEFT}` del mensaje de bienvenida ("0s" la desactiva)
+    #[serde(with = "humantime_serde", default = "GroupSettings::default_countdown_interval")]
+    pub countdown_interval: Duration,
+
+    /// Mensajes de un usuario pendiente que se borran antes de banearlo (sin límite si no se indica)
+    pub ban_after_messages: Option<u32>,
+
+    /// Veces que un usuario pendiente puede pedir un enlace nuevo y más tiempo (0 lo desactiva)
+    #[serde(default = "GroupSettings::default_max_extensions")]
+    pub max_extensions: u32,
+}
+
+impl GroupSettings {
+    fn default_credential_types() -> Vec<String> {
+        vec!["orb".to_string(), "phone".to_string()]
+    }
+
+    fn default_max_extensions() -> u32 {
+        1
+    }
+
+    fn default_countdown_interval() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    /// Comprueba la plantilla de `messages.new_user_template`, el archivo de `welcome_media`
+    /// y que `use_web_app` vaya con `verify_in_private`
+    pub fn validate(&self) -> Result<(), String> {
+        // En el grupo los botones son enlaces normales, sin `initData`, y la verificación fallaría
+        if self.use_web_app && !self.verify_in_private {
+            return Err("use_web_app requires verify_in_private".to_string());
+        }
+
+        for webhook in &self.webhooks {
+            webhook.validate()?;
+        }
+
+        if let Some(template) = &self.messages.new_user_template {
+            WelcomeTemplate::new(template, self.messages.parse_mode)
+                .validate()
+                .map_err(|e| format!("invalid messages.new_user_template: {e}"))?;
+        }
+
+        if let Some(WelcomeMedia { source: MediaSource::Path(path), .. }) = &self.welcome_media {
+            if !path.is_file() {
+                return Err(format!("welcome_media.path {} is not a file", path.display()));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for GroupSettings {
@@ -95,53 +468,158 @@ impl Default for GroupSettings {
         Self {
             chat_name: None,
             admin_ids: None,
-            messages: MessagesText::default(),
+            messages: GroupMessages::default(),
+            rules_url: None,
+            welcome_media: None,
+            auto_delete: AutoDeleteSettings::default(),
             ban_after: Duration::from_secs(60 * 5),
             language: None,
+            verify_in_private: false,
+            use_web_app: false,
+            page: PageSettings::default(),
+            credential_types: Self::default_credential_types(),
+            webhooks: Vec::new(),
+            log_chat_id: None,
+            countdown_interval: Self::default_countdown_interval(),
+            max_extensions: Self::default_max_extensions(),
+            ban_after_messages: None,
         }
     }
 }
 
-/// Textos personalizados por grupo o idioma
-#[derive(Debug, Clone, Deserialize, PartialEq)]
+/// Adjunto del mensaje de bienvenida (`welcome_media = { type = "photo", url = "..." }`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WelcomeMedia {
+    #[serde(rename = "type")]
+    pub kind: MediaKind,
+
+    /// Origen del archivo: `file_id`, `url` o `path`
+    #[serde(flatten)]
+    pub source: MediaSource,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    Photo,
+    /// GIF o vídeo MPEG-4 sin sonido
+    Animation,
+    Video,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaSource {
+    /// Archivo ya subido a Telegram
+    FileId(String),
+    Url(Url),
+    /// Archivo local, que se sube con cada mensaje
+    Path(PathBuf),
+}
+
+/// Mensajes que el bot borra del grupo (`auto_delete.*`); nada se borra por defecto
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AutoDeleteSettings {
+    /// Mensajes de servicio "X se unió" y "X salió del grupo"
+    #[serde(default)]
+    pub service_messages: bool,
+
+    /// Espera antes de borrar los comandos (`/check`, `/help`, `/log`) y las respuestas del bot
+    #[serde(with = "humantime_serde", default)]
+    pub commands: Option<Duration>,
+
+    /// Espera antes de borrar los avisos de error del bot, si es distinta a la de `commands`
+    #[serde(with = "humantime_serde", default)]
+    pub failure_notices: Option<Duration>,
+}
+
+/// Marca visual de la página de verificación de un grupo
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PageSettings {
+    pub logo_url: Option<Url>,
+    pub text_color: Option<String>,
+    pub primary_color: Option<String>,
+    pub background_color: Option<String>,
+
+    /// Texto explicativo; si no se indica se usa el de la traducción
+    pub description: Option<String>,
+
+    #[serde(default)]
+    pub footer_links: Vec<FooterLink>,
+
+    /// Plantilla HTML propia que reemplaza a la plantilla por defecto
+    pub template: Option<PathBuf>,
+}
+
+/// Enlace del pie de la página de verificación
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FooterLink {
+    pub label: String,
+    pub url: Url,
+}
+
+/// Textos personalizados de un grupo (`messages.*`), todos opcionales
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct GroupMessages {
+    pub new_user_template: Option<String>,
+    pub unauthorized_group: Option<String>,
+    pub successfully_verified: Option<String>,
+    pub user_doesnt_match_error: Option<String>,
+
+    /// Formato de `new_user_template`: "html" (por defecto) o "markdown_v2"
+    #[serde(default)]
+    pub parse_mode: TemplateFormat,
+}
+
+impl GroupMessages {
+    /// Completa los textos que falten con los de la traducción
+    pub fn resolve(&self, translation: &Translation) -> MessagesText {
+        let defaults = MessagesText::from_translation(translation);
+
+        // `parse_mode` solo se aplica a la plantilla propia; las traducciones están en HTML
+        let new_user_template = match &self.new_user_template {
+            Some(template) => WelcomeTemplate::new(template, self.parse_mode),
+            None => defaults.new_user_template,
+        };
+
+        MessagesText {
+            new_user_template,
+            unauthorized_group: self
+                .unauthorized_group
+                .clone()
+                .unwrap_or(defaults.unauthorized_group),
+            successfully_verified: self
+                .successfully_verified
+                .clone()
+                .unwrap_or(defaults.successfully_verified),
+            user_doesnt_match_error: self
+                .user_doesnt_match_error
+                .clone()
+                .unwrap_or(defaults.user_doesnt_match_error),
+        }
+    }
+}
+
+/// Textos de un grupo ya resueltos para su idioma
+#[derive(Debug, Clone)]
 pub struct MessagesText {
-    pub new_user_template: String,
+    pub new_user_template: WelcomeTemplate,
     pub unauthorized_group: String,
     pub successfully_verified: String,
     pub user_doesnt_match_error: String,
 }
 
 impl MessagesText {
-    /// Crea mensaje de bienvenida reemplazando etiquetas dinámicas
-    pub fn create_welcome_msg(&self, user: &User, chat_name: &str) -> String {
-        self.new_user_template
-            .replace(
-                "{TAGUSER}",
-                &user
-                    .mention()
-                    .unwrap_or_else(|| format!("[{}](tg://user?id={})", user.full_name(), user.id)),
-            )
-            .replace("{CHATNAME}", &escape(chat_name))
-    }
-
     /// Genera una estructura `MessagesText` desde las traducciones cargadas en I18n
     pub fn from_translation(translation: &Translation) -> Self {
         Self {
-            new_user_template: translation.new_user_template.clone(),
+            new_user_template: WelcomeTemplate::new(
+                &translation.new_user_template,
+                TemplateFormat::Html,
+            ),
             unauthorized_group: translation.unauthorized_group.clone(),
             successfully_verified: translation.successfully_verified.clone(),
             user_doesnt_match_error: translation.user_doesnt_match_error.clone(),
         }
     }
 }
-
-impl Default for MessagesText {
-    fn default() -> Self {
-        Self {
-            user_doesnt_match_error: "❌ This message isn't for you".to_string(),
-            unauthorized_group: "❌ You can't use this bot on this group. Bye!".to_string(),
-            successfully_verified: "✅ Verified with World ID. Welcome to the group!".to_string(),
-            new_user_template: "👋 gm {TAGUSER}! Welcome to {CHATNAME}.\nTo access the group, please verify your account with World ID.".to_string(),
-        }
-    }
-}
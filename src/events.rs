@@ -0,0 +1,184 @@
+use humantime_serde::re::humantime;
+use std::{sync::Arc, time::Duration};
+use teloxide::types::{ChatId, User, UserId};
+
+use crate::{
+	analytics::{Analytics, AnalyticsEvent, EventKind},
+	audit::{Actor, AuditAction, AuditEntry, AuditLog},
+	log_channel::{LogChannel, LogNotice},
+	metrics::Metrics,
+	permissions::Permission,
+	webhooks::{WebhookEvent, Webhooks},
+};
+
+/// Reparte los eventos de moderación entre métricas, analítica, webhooks, el registro de
+/// auditoría y el canal de registro del grupo
+#[derive(Clone)]
+pub struct Events {
+	pub audit: Arc<AuditLog>,
+	pub metrics: Arc<Metrics>,
+	pub analytics: Arc<Analytics>,
+	pub webhooks: Arc<Webhooks>,
+	pub log_channel: Arc<LogChannel>,
+}
+
+impl Events {
+	pub fn joined(&self, chat_id: ChatId, user: &User, lang: &str) {
+		self.metrics.joins.inc();
+		self.analytics
+			.track(AnalyticsEvent::new(EventKind::Joined, chat_id, user.id).lang(lang));
+		self.audit
+			.record(AuditEntry::new(Actor::Bot, AuditAction::Joined, chat_id, user.id));
+		self.log_channel
+			.notify(chat_id, user.id, Some(user), LogNotice::Joined);
+	}
+
+	/// Usuario silenciado al unirse, hasta que se verifique
+	pub fn restricted(&self, chat_id: ChatId, user_id: UserId) {
+		self.audit.record(
+			AuditEntry::new(Actor::Bot, AuditAction::Restricted, chat_id, user_id)
+				.reason("pending verification"),
+		);
+	}
+
+	/// Usuario pendiente eliminado del grupo: por el bot al expirar el plazo o manualmente
+	pub fn removed(&self, chat_id: ChatId, user: &User, lang: &str, actor: Actor) {
+		let (action, reason, notice) = match actor {
+			Actor::Bot => {
+				self.metrics.timeouts.inc();
+				self.analytics
+					.track(AnalyticsEvent::new(EventKind::TimedOut, chat_id, user.id).lang(lang));
+
+				(AuditAction::TimedOut, "timeout", LogNotice::TimedOut)
+			},
+			_ => (AuditAction::Rejected, "rejected", LogNotice::Rejected(actor)),
+		};
+
+		self.audit
+			.record(AuditEntry::new(actor, action, chat_id, user.id));
+		self.webhooks
+			.dispatch(WebhookEvent::Removed, chat_id, user.id, Some(reason));
+		self.log_channel
+			.notify(chat_id, user.id, Some(user), notice);
+	}
+
+	pub fn kicked(&self, chat_id: ChatId, user_id: UserId, lang: &str, actor: Actor) {
+		self.metrics.kicks.inc();
+		self.analytics
+			.track(AnalyticsEvent::new(EventKind::Kicked, chat_id, user_id).lang(lang));
+		self.audit
+			.record(AuditEntry::new(actor, AuditAction::Kicked, chat_id, user_id));
+	}
+
+	/// Usuario baneado. Si seguía pendiente (`pending`, con su idioma), su solicitud se cierra
+	/// como al expulsarlo
+	pub fn banned(
+		&self,
+		chat_id: ChatId,
+		user_id: UserId,
+		pending: Option<(&User, &str)>,
+		actor: Actor,
+	) {
+		if let Some((_, lang)) = pending {
+			self.metrics.kicks.inc();
+			self.analytics
+				.track(AnalyticsEvent::new(EventKind::Kicked, chat_id, user_id).lang(lang));
+			self.webhooks
+				.dispatch(WebhookEvent::Removed, chat_id, user_id, Some("banned"));
+		}
+
+		self.audit
+			.record(AuditEntry::new(actor, AuditAction::Banned, chat_id, user_id));
+		self.log_channel.notify(
+			chat_id,
+			user_id,
+			pending.map(|(user, _)| user),
+			LogNotice::Banned(actor),
+		);
+	}
+
+	/// Plazo de verificación de un usuario pendiente extendido en `by`
+	pub fn extended(&self, chat_id: ChatId, user_id: UserId, actor: Actor, by: Duration) {
+		self.audit.record(
+			AuditEntry::new(actor, AuditAction::Extended, chat_id, user_id)
+				.reason(&humantime::format_duration(by).to_string()),
+		);
+	}
+
+	/// Mensaje del bot eliminado; `user_id` es el usuario cuya resolución lo provocó
+	pub fn message_deleted(&self, chat_id: ChatId, user_id: UserId, reason: &str) {
+		self.audit.record(
+			AuditEntry::new(Actor::Bot, AuditAction::MessageDeleted, chat_id, user_id)
+				.reason(reason),
+		);
+	}
+
+	/// Mensaje de un usuario pendiente eliminado; `attempts` cuenta los borrados hasta ahora
+	pub fn pending_message_deleted(&self, chat_id: ChatId, user_id: UserId, attempts: u32) {
+		self.metrics.pending_messages_deleted.inc();
+		self.audit.record(
+			AuditEntry::new(Actor::Bot, AuditAction::MessageDeleted, chat_id, user_id)
+				.reason(&format!("message from pending user (attempt {attempts})")),
+		);
+	}
+
+	/// Cambiaron los permisos de administrador que le faltan al bot en el grupo
+	pub fn permissions_changed(&self, chat_id: ChatId, missing: &[Permission]) {
+		for permission in Permission::ALL {
+			self.metrics
+				.missing_permissions
+				.with_label_values(&[&chat_id.to_string(), permission.as_str()])
+				.set(missing.contains(&permission).into());
+		}
+		self.log_channel.permissions_changed(chat_id, missing);
+	}
+
+	/// Usuario aprobado: por el bot tras una prueba de World ID o manualmente
+	pub fn approved(
+		&self,
+		chat_id: ChatId,
+		user: &User,
+		actor: Actor,
+		credential_type: Option<&str>,
+	) {
+		let (action, notice) = match actor {
+			Actor::Bot => (AuditAction::Verified, LogNotice::Verified { credential_type }),
+			_ => (AuditAction::Approved, LogNotice::Approved(actor)),
+		};
+
+		let mut entry = AuditEntry::new(actor, action, chat_id, user.id);
+		if let Some(credential_type) = credential_type {
+			entry = entry.reason(credential_type);
+		}
+
+		self.audit.record(entry);
+		self.webhooks
+			.dispatch(WebhookEvent::Verified, chat_id, user.id, None);
+		self.log_channel
+			.notify(chat_id, user.id, Some(user), notice);
+	}
+
+	/// No se pudo enviar la bienvenida y el usuario quedó sin restricción y sin verificar;
+	/// `failures` son los errores anotados en su solicitud
+	pub fn released(&self, chat_id: ChatId, user: &User, failures: &[String]) {
+		let reason = failures.join("; ");
+		self.audit.record(
+			AuditEntry::new(Actor::Bot, AuditAction::Released, chat_id, user.id).reason(&reason),
+		);
+		self.log_channel
+			.notify(chat_id, user.id, Some(user), LogNotice::Released { reason: &reason });
+	}
+
+	/// Intento de verificación rechazado de un usuario pendiente (`reason` es el código de `VerifyStatus`)
+	pub fn failed(&self, chat_id: ChatId, user: &User, reason: &str) {
+		self.metrics
+			.verifications_failed
+			.with_label_values(&[reason])
+			.inc();
+		self.audit.record(
+			AuditEntry::new(Actor::Bot, AuditAction::Failed, chat_id, user.id).reason(reason),
+		);
+		self.log_channel
+			.notify(chat_id, user.id, Some(user), LogNotice::Failed { reason });
+	}
+}
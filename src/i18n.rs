@@ -1,8 +1,8 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use teloxide::types::User;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Translation {
     pub new_user_template: String,
     pub unauthorized_group: String,
@@ -16,12 +16,50 @@ pub struct Translation {
     pub alert_success: String,
     pub alert_already_used: String,
     pub alert_error: String,
+    pub verify_private_prompt: String,
+    pub verify_private_invalid: String,
+    pub page_description: String,
+    pub error_expired: String,
+    pub error_not_found: String,
+    pub error_invalid_proof: String,
+    pub error_unauthorized: String,
+    pub error_already_verified: String,
+    pub error_insufficient_credential: String,
+    pub admin_only: String,
+    pub log_header: String,
+    pub log_empty: String,
+    pub log_joined: String,
+    pub log_verified: String,
+    pub log_approved: String,
+    pub log_timed_out: String,
+    pub log_rejected: String,
+    pub log_failed: String,
+    pub log_banned: String,
+    pub log_approve_button: String,
+    pub log_ban_button: String,
+    pub callback_admin_only: String,
+    pub callback_done: String,
+    pub callback_not_pending: String,
+    pub welcome_approve_button: String,
+    pub welcome_kick_button: String,
+    pub welcome_extend_button: String,
+    pub callback_extended: String,
+    pub resend_button: String,
+    pub resend_done: String,
+    pub resend_limit_reached: String,
+    pub help_group_check_missing: String,
+    pub permission_restrict_members: String,
+    pub permission_delete_messages: String,
+    pub permission_invite_users: String,
+    pub log_missing_permissions: String,
+    pub log_permissions_restored: String,
+    pub log_released: String,
 }
 
 impl Default for Translation {
     fn default() -> Self {
         Self {
-            new_user_template: "👋 gm {TAGUSER}! Welcome to {CHATNAME}.\nTo access the group, please verify your account with World ID.".to_string(),
+            new_user_template: "👋 gm {TAGUSER}! Welcome to {CHATNAME}.\nTo access the group, please verify your account with World ID.\n⏳ Time left: {TIMELEFT}".to_string(),
             user_doesnt_match_error: "❌ This message isn't for you".to_string(),
             successfully_verified: "✅ Verified with World ID. Welcome to the group!".to_string(),
             unauthorized_group: "❌ You can't use this bot on this group. Bye!".to_string(),
@@ -33,6 +71,44 @@ impl Default for Translation {
             alert_success: "Successfully verified! You can now close this and go back to the group.".to_string(),
             alert_already_used: "This World ID has already been used to join this group. You can't do it again!".to_string(),
             alert_error: "Something went wrong, please try again later.".to_string(),
+            verify_private_prompt: "To get access to {CHATNAME}, please verify your account with World ID using the button below.".to_string(),
+            verify_private_invalid: "This verification link is invalid or has expired.".to_string(),
+            page_description: "This group is protected by World ID. Prove you're a unique human to get access, no personal data is shared with the group.".to_string(),
+            error_expired: "This verification has expired. Please leave the group and join again.".to_string(),
+            error_not_found: "We couldn't find a pending verification for you in this group.".to_string(),
+            error_invalid_proof: "Your World ID proof couldn't be verified. Please try again.".to_string(),
+            error_unauthorized: "This verification must be completed from Telegram.".to_string(),
+            error_already_verified: "You're already verified in this group!".to_string(),
+            error_insufficient_credential: "This group requires a stronger World ID credential than the one you used.".to_string(),
+            admin_only: "Only group admins can use this command.".to_string(),
+            log_header: "Latest moderation actions:".to_string(),
+            log_empty: "There are no moderation actions in this group yet.".to_string(),
+            log_joined: "👤 {USER} joined and must verify with World ID".to_string(),
+            log_verified: "✅ {USER} verified with World ID ({CREDENTIAL})".to_string(),
+            log_approved: "✅ {USER} was approved by {ACTOR}".to_string(),
+            log_timed_out: "⏰ {USER} didn't verify in time and was removed".to_string(),
+            log_rejected: "🚫 {USER} was removed by {ACTOR}".to_string(),
+            log_failed: "⚠️ {USER} failed to verify ({REASON})".to_string(),
+            log_banned: "⛔ {USER} was banned by {ACTOR}".to_string(),
+            log_approve_button: "Approve".to_string(),
+            log_ban_button: "Ban".to_string(),
+            callback_admin_only: "Only group admins can do this.".to_string(),
+            callback_done: "Done!".to_string(),
+            callback_not_pending: "This user isn't pending verification anymore.".to_string(),
+            welcome_approve_button: "✅ Approve".to_string(),
+            welcome_kick_button: "🚫 Kick".to_string(),
+            welcome_extend_button: "⏳ Extend time".to_string(),
+            callback_extended: "Added {TIME} to the deadline.".to_string(),
+            resend_button: "🔄 Resend link".to_string(),
+            resend_done: "You have {TIME} more. Continue the verification in the chat with the bot.".to_string(),
+            resend_limit_reached: "You can't get more time for this verification.".to_string(),
+            help_group_check_missing: "Bot is an admin, but it's missing these rights: {PERMISSIONS}. Please, enable them and try again.".to_string(),
+            permission_restrict_members: "Ban users".to_string(),
+            permission_delete_messages: "Delete messages".to_string(),
+            permission_invite_users: "Add members".to_string(),
+            log_missing_permissions: "⚠️ The bot is missing admin rights: {PERMISSIONS}. New members can't be verified until they're enabled.".to_string(),
+            log_permissions_restored: "✅ The bot has all the admin rights it needs again.".to_string(),
+            log_released: "⚠️ {USER} was let in without verifying, because the welcome message couldn't be sent ({REASON})".to_string(),
         }
     }
 }
@@ -52,7 +128,7 @@ impl I18n {
         
         // Spanish
         translations.insert("es".to_string(), Translation {
-            new_user_template: "👋 ¡Hola {TAGUSER}! Bienvenido a {CHATNAME}.\nPara acceder al grupo, por favor verifica tu cuenta con World ID.".to_string(),
+            new_user_template: "👋 ¡Hola {TAGUSER}! Bienvenido a {CHATNAME}.\nPara acceder al grupo, por favor verifica tu cuenta con World ID.\n⏳ Tiempo restante: {TIMELEFT}".to_string(),
             user_doesnt_match_error: "❌ Este mensaje no es para ti".to_string(),
             successfully_verified: "✅ Verificado con World ID. ¡Bienvenido al grupo!".to_string(),
             unauthorized_group: "❌ No puedes usar este bot en este grupo. ¡Adiós!".to_string(),
@@ -64,11 +140,49 @@ impl I18n {
             alert_success: "¡Verificación exitosa! Ahora puedes cerrar esto y volver al grupo.".to_string(),
             alert_already_used: "Este World ID ya ha sido usado para unirse a este grupo. ¡No puedes hacerlo de nuevo!".to_string(),
             alert_error: "Algo salió mal, por favor intenta nuevamente más tarde.".to_string(),
+            verify_private_prompt: "Para acceder a {CHATNAME}, por favor verifica tu cuenta con World ID usando el botón de abajo.".to_string(),
+            verify_private_invalid: "Este enlace de verificación no es válido o ha expirado.".to_string(),
+            page_description: "Este grupo está protegido con World ID. Demuestra que eres un humano único para acceder, no se comparte ningún dato personal con el grupo.".to_string(),
+            error_expired: "Esta verificación ha expirado. Por favor sal del grupo y vuelve a unirte.".to_string(),
+            error_not_found: "No encontramos una verificación pendiente para ti en este grupo.".to_string(),
+            error_invalid_proof: "No pudimos verificar tu prueba de World ID. Por favor intenta nuevamente.".to_string(),
+            error_unauthorized: "Esta verificación debe completarse desde Telegram.".to_string(),
+            error_already_verified: "¡Ya estás verificado en este grupo!".to_string(),
+            error_insufficient_credential: "Este grupo requiere una credencial de World ID más fuerte que la que usaste.".to_string(),
+            admin_only: "Solo los administradores del grupo pueden usar este comando.".to_string(),
+            log_header: "Últimas acciones de moderación:".to_string(),
+            log_empty: "Todavía no hay acciones de moderación en este grupo.".to_string(),
+            log_joined: "👤 {USER} se unió y debe verificarse con World ID".to_string(),
+            log_verified: "✅ {USER} se verificó con World ID ({CREDENTIAL})".to_string(),
+            log_approved: "✅ {USER} fue aprobado por {ACTOR}".to_string(),
+            log_timed_out: "⏰ {USER} no se verificó a tiempo y fue expulsado".to_string(),
+            log_rejected: "🚫 {USER} fue expulsado por {ACTOR}".to_string(),
+            log_failed: "⚠️ {USER} no pudo verificarse ({REASON})".to_string(),
+            log_banned: "⛔ {USER} fue baneado por {ACTOR}".to_string(),
+            log_approve_button: "Aprobar".to_string(),
+            log_ban_button: "Banear".to_string(),
+            callback_admin_only: "Solo los administradores del grupo pueden hacer esto.".to_string(),
+            callback_done: "¡Hecho!".to_string(),
+            callback_not_pending: "Este usuario ya no tiene una verificación pendiente.".to_string(),
+            welcome_approve_button: "✅ Aprobar".to_string(),
+            welcome_kick_button: "🚫 Expulsar".to_string(),
+            welcome_extend_button: "⏳ Dar más tiempo".to_string(),
+            callback_extended: "Se añadieron {TIME} al plazo.".to_string(),
+            resend_button: "🔄 Reenviar enlace".to_string(),
+            resend_done: "Tienes {TIME} más. Continúa la verificación en el chat con el bot.".to_string(),
+            resend_limit_reached: "No puedes pedir más tiempo para esta verificación.".to_string(),
+            help_group_check_missing: "El bot es administrador, pero le faltan estos permisos: {PERMISSIONS}. Por favor, actívalos e intenta nuevamente.".to_string(),
+            permission_restrict_members: "Suspender usuarios".to_string(),
+            permission_delete_messages: "Eliminar mensajes".to_string(),
+            permission_invite_users: "Añadir miembros".to_string(),
+            log_missing_permissions: "⚠️ Al bot le faltan permisos de administrador: {PERMISSIONS}. No se podrá verificar a los nuevos miembros hasta que se activen.".to_string(),
+            log_permissions_restored: "✅ El bot vuelve a tener todos los permisos de administrador que necesita.".to_string(),
+            log_released: "⚠️ {USER} entró sin verificarse, porque no se pudo enviar el mensaje de bienvenida ({REASON})".to_string(),
         });
         
         // Portuguese
         translations.insert("pt".to_string(), Translation {
-            new_user_template: "👋 Olá {TAGUSER}! Bem-vindo ao {CHATNAME}.\nPara acessar o grupo, por favor verifique sua conta com World ID.".to_string(),
+            new_user_template: "👋 Olá {TAGUSER}! Bem-vindo ao {CHATNAME}.\nPara acessar o grupo, por favor verifique sua conta com World ID.\n⏳ Tempo restante: {TIMELEFT}".to_string(),
             user_doesnt_match_error: "❌ Esta mensagem não é para você".to_string(),
             successfully_verified: "✅ Verificado com World ID. Bem-vindo ao grupo!".to_string(),
             unauthorized_group: "❌ Você não pode usar este bot neste grupo. Tchau!".to_string(),
@@ -80,6 +194,44 @@ impl I18n {
             alert_success: "Verificação bem-sucedida! Agora você pode fechar isso e voltar ao grupo.".to_string(),
             alert_already_used: "Este World ID já foi usado para entrar neste grupo. Você não pode fazer isso novamente!".to_string(),
             alert_error: "Algo deu errado, por favor tente novamente mais tarde.".to_string(),
+            verify_private_prompt: "Para acessar {CHATNAME}, por favor verifique sua conta com World ID usando o botão abaixo.".to_string(),
+            verify_private_invalid: "Este link de verificação é inválido ou expirou.".to_string(),
+            page_description: "Este grupo é protegido pelo World ID. Prove que você é um humano único para acessar, nenhum dado pessoal é compartilhado com o grupo.".to_string(),
+            error_expired: "Esta verificação expirou. Por favor, saia do grupo e entre novamente.".to_string(),
+            error_not_found: "Não encontramos uma verificação pendente para você neste grupo.".to_string(),
+            error_invalid_proof: "Não foi possível verificar sua prova do World ID. Por favor, tente novamente.".to_string(),
+            error_unauthorized: "Esta verificação deve ser concluída pelo Telegram.".to_string(),
+            error_already_verified: "Você já está verificado neste grupo!".to_string(),
+            error_insufficient_credential: "Este grupo exige uma credencial do World ID mais forte do que a que você usou.".to_string(),
+            admin_only: "Apenas os administradores do grupo podem usar este comando.".to_string(),
+            log_header: "Últimas ações de moderação:".to_string(),
+            log_empty: "Ainda não há ações de moderação neste grupo.".to_string(),
+            log_joined: "👤 {USER} entrou e precisa se verificar com World ID".to_string(),
+            log_verified: "✅ {USER} se verificou com World ID ({CREDENTIAL})".to_string(),
+            log_approved: "✅ {USER} foi aprovado por {ACTOR}".to_string(),
+            log_timed_out: "⏰ {USER} não se verificou a tempo e foi removido".to_string(),
+            log_rejected: "🚫 {USER} foi removido por {ACTOR}".to_string(),
+            log_failed: "⚠️ {USER} não conseguiu se verificar ({REASON})".to_string(),
+            log_banned: "⛔ {USER} foi banido por {ACTOR}".to_string(),
+            log_approve_button: "Aprovar".to_string(),
+            log_ban_button: "Banir".to_string(),
+            callback_admin_only: "Apenas os administradores do grupo podem fazer isso.".to_string(),
+            callback_done: "Feito!".to_string(),
+            callback_not_pending: "Este usuário não tem mais uma verificação pendente.".to_string(),
+            welcome_approve_button: "✅ Aprovar".to_string(),
+            welcome_kick_button: "🚫 Remover".to_string(),
+            welcome_extend_button: "⏳ Dar mais tempo".to_string(),
+            callback_extended: "Foram adicionados {TIME} ao prazo.".to_string(),
+            resend_button: "🔄 Reenviar link".to_string(),
+            resend_done: "Você tem mais {TIME}. Continue a verificação no chat com o bot.".to_string(),
+            resend_limit_reached: "Você não pode pedir mais tempo para esta verificação.".to_string(),
+            help_group_check_missing: "O bot é administrador, mas faltam estas permissões: {PERMISSIONS}. Por favor, ative-as e tente novamente.".to_string(),
+            permission_restrict_members: "Banir usuários".to_string(),
+            permission_delete_messages: "Apagar mensagens".to_string(),
+            permission_invite_users: "Adicionar membros".to_string(),
+            log_missing_permissions: "⚠️ Faltam permissões de administrador ao bot: {PERMISSIONS}. Os novos membros não poderão ser verificados até que sejam ativadas.".to_string(),
+            log_permissions_restored: "✅ O bot voltou a ter todas as permissões de administrador de que precisa.".to_string(),
+            log_released: "⚠️ {USER} entrou sem se verificar, porque não foi possível enviar a mensagem de boas-vindas ({REASON})".to_string(),
         });
         
         Self {
@@ -106,9 +258,42 @@ impl I18n {
     self.default_lang.as_str()
 }
 
-    
+
+    /// Devuelve el primer idioma disponible de la cadena de candidatos, o el idioma por defecto
+    pub fn resolve<'a>(&self, candidates: impl IntoIterator<Item = Option<&'a str>>) -> &str {
+        candidates
+            .into_iter()
+            .flatten()
+            .find_map(|lang| self.translations.get_key_value(lang))
+            .map_or(self.default_lang.as_str(), |(lang, _)| lang.as_str())
+    }
+
+    /// Elige el idioma disponible con mayor prioridad de una cabecera `Accept-Language`
+    pub fn parse_accept_language(&self, header: &str) -> Option<&str> {
+        let mut langs = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.trim().split(';');
+                let lang = parts.next()?.split('-').next()?.trim().to_lowercase();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+
+                Some((lang, quality))
+            })
+            .collect::<Vec<_>>();
+
+        langs.sort_by(|a, b| b.1.total_cmp(&a.1));
+        langs
+            .into_iter()
+            .find_map(|(lang, _)| self.translations.get_key_value(lang.as_str()))
+            .map(|(lang, _)| lang.as_str())
+    }
+
     pub fn available_languages(&self) -> Vec<String> {
-        self.translations.keys().cloned().collect()
+        let mut langs: Vec<String> = self.translations.keys().cloned().collect();
+        langs.sort();
+        langs
     }
 }
 
@@ -117,3 +302,35 @@ impl Default for I18n {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_highest_quality_available_language() {
+        let i18n = I18n::new();
+        assert_eq!(i18n.parse_accept_language("pt-BR,pt;q=0.9,en;q=0.8"), Some("pt"));
+        assert_eq!(i18n.parse_accept_language("en;q=0.5, fr-FR, es;q=0.8"), Some("es"));
+    }
+
+    #[test]
+    fn ignores_case_and_region() {
+        let i18n = I18n::new();
+        assert_eq!(i18n.parse_accept_language("ES-mx"), Some("es"));
+    }
+
+    #[test]
+    fn skips_entries_with_malformed_quality() {
+        let i18n = I18n::new();
+        assert_eq!(i18n.parse_accept_language("es;q=high, en;q=0.1"), Some("en"));
+    }
+
+    #[test]
+    fn returns_none_without_available_language() {
+        let i18n = I18n::new();
+        assert_eq!(i18n.parse_accept_language("de, fr;q=0.9"), None);
+        assert_eq!(i18n.parse_accept_language(""), None);
+        assert_eq!(i18n.parse_accept_language("*"), None);
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+	cmp::{Ordering, Reverse},
+	collections::BinaryHeap,
+	time::Duration,
+};
+use teloxide::{
+	requests::Requester,
+	types::{ChatId, MessageId},
+};
+use tokio::{
+	sync::mpsc,
+	time::{sleep_until, Instant},
+};
+
+use crate::bot::Bot;
+
+/// Tarea diferida
+#[derive(Debug, Clone, Copy)]
+pub enum Job {
+	DeleteMessage { chat_id: ChatId, message_id: MessageId },
+}
+
+/// Tarea pendiente, ordenada por hora de ejecución y después por orden de llegada
+#[derive(Debug)]
+struct Scheduled {
+	run_at: Instant,
+	seq: u64,
+	job: Job,
+}
+
+impl Ord for Scheduled {
+	fn cmp(&self, other: &Self) -> Ordering {
+		(self.run_at, self.seq).cmp(&(other.run_at, other.seq))
+	}
+}
+
+impl PartialOrd for Scheduled {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl PartialEq for Scheduled {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for Scheduled {}
+
+/// Cola de tareas diferidas (borrado de mensajes del bot, de comandos y de servicio),
+/// atendida por una sola tarea en segundo plano. Las tareas pendientes se pierden al reiniciar
+#[derive(Debug, Clone)]
+pub struct Jobs {
+	tx: mpsc::UnboundedSender<(Instant, Job)>,
+}
+
+impl Jobs {
+	/// Crea la cola y lanza la tarea que la atiende
+	pub fn start(bot: Bot) -> Self {
+		let (tx, rx) = mpsc::unbounded_channel();
+		tokio::spawn(run(bot, rx));
+
+		Self { tx }
+	}
+
+	pub fn schedule(&self, delay: Duration, job: Job) {
+		if self.tx.send((Instant::now() + delay, job)).is_err() {
+			log::error!("The job queue is closed, dropping {job:?}");
+		}
+	}
+
+	/// Programa el borrado de un mensaje
+	pub fn delete_message(&self, chat_id: ChatId, message_id: MessageId, delay: Duration) {
+		self.schedule(delay, Job::DeleteMessage { chat_id, message_id });
+	}
+}
+
+async fn run(bot: Bot, mut rx: mpsc::UnboundedReceiver<(Instant, Job)>) {
+	let mut queue = BinaryHeap::new();
+	let mut seq = 0;
+
+	loop {
+		let next = queue.peek().map(|Reverse(scheduled): &Reverse<Scheduled>| scheduled.run_at);
+
+		tokio::select! {
+			received = rx.recv() => {
+				let Some((run_at, job)) = received else {
+					break;
+				};
+
+				seq += 1;
+				queue.push(Reverse(Scheduled { run_at, seq, job }));
+			},
+			() = sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+				let Some(Reverse(Scheduled { job, .. })) = queue.pop() else {
+					continue;
+				};
+
+				tokio::spawn(execute(bot.clone(), job));
+			},
+		}
+	}
+}
+
+async fn execute(bot: Bot, job: Job) {
+	match job {
+		Job::DeleteMessage { chat_id, message_id } => {
+			// El mensaje puede haberse borrado ya a mano
+			if let Err(e) = bot.delete_message(chat_id, message_id).await {
+				log::debug!("Couldn't delete message {message_id} in {chat_id}: {e}");
+			}
+		},
+	}
+}
@@ -0,0 +1,224 @@
+use std::sync::Arc;
+use teloxide::{
+	payloads::SendMessageSetters,
+	requests::Requester,
+	types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup, ParseMode, User, UserId},
+	utils::html,
+};
+
+use crate::{
+	audit::Actor,
+	bot::Bot,
+	config::{AppConfig, GroupSettings},
+	i18n::I18n,
+	permissions::Permission,
+};
+
+/// Prefijo de los `callback_data` de los botones del canal de registro
+const CALLBACK_PREFIX: &str = "log";
+
+/// Evento publicado en el canal de registro
+#[derive(Debug, Clone, Copy)]
+pub enum LogNotice<'a> {
+	Joined,
+	Verified { credential_type: Option<&'a str> },
+	Approved(Actor),
+	TimedOut,
+	Rejected(Actor),
+	Failed { reason: &'a str },
+	/// El usuario quedó libre sin verificarse por un error de Telegram
+	Released { reason: &'a str },
+	Banned(Actor),
+}
+
+/// Acción de los botones "aprobar" y "banear"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogAction {
+	Approve,
+	Ban,
+}
+
+impl LogAction {
+	const fn as_str(&self) -> &'static str {
+		match self {
+			Self::Approve => "approve",
+			Self::Ban => "ban",
+		}
+	}
+
+	/// `callback_data` con el formato `log:<acción>:<chat_id>:<user_id>`
+	fn callback_data(&self, chat_id: ChatId, user_id: UserId) -> String {
+		format!("{CALLBACK_PREFIX}:{}:{chat_id}:{user_id}", self.as_str())
+	}
+
+	pub fn parse_callback(data: &str) -> Option<(Self, ChatId, UserId)> {
+		let mut parts = data.split(':');
+		if parts.next()? != CALLBACK_PREFIX {
+			return None;
+		}
+
+		let action = match parts.next()? {
+			"approve" => Self::Approve,
+			"ban" => Self::Ban,
+			_ => return None,
+		};
+		let chat_id = ChatId(parts.next()?.parse().ok()?);
+		let user_id = UserId(parts.next()?.parse().ok()?);
+
+		Some((action, chat_id, user_id))
+	}
+}
+
+/// Publica avisos de moderación en el canal de registro de cada grupo (`log_chat_id`)
+pub struct LogChannel {
+	bot: Bot,
+	config: AppConfig,
+	i18n: Arc<I18n>,
+}
+
+impl LogChannel {
+	pub fn new(bot: Bot, config: AppConfig, i18n: Arc<I18n>) -> Self {
+		Self { bot, config, i18n }
+	}
+
+	/// Envía el aviso en segundo plano si el grupo tiene canal de registro
+	pub fn notify(&self, chat_id: ChatId, user_id: UserId, user: Option<&User>, notice: LogNotice) {
+		let settings = self.config.groups_config.get(chat_id);
+		let Some(log_chat_id) = settings.log_chat_id else {
+			return;
+		};
+
+		let translation = self.i18n.get(self.i18n.resolve([settings.language.as_deref()]));
+		let (template, actions): (_, &[LogAction]) = match notice {
+			LogNotice::Joined => (&translation.log_joined, &[LogAction::Approve, LogAction::Ban]),
+			LogNotice::Verified { .. } => (&translation.log_verified, &[LogAction::Ban]),
+			LogNotice::Approved(_) => (&translation.log_approved, &[LogAction::Ban]),
+			LogNotice::TimedOut => (&translation.log_timed_out, &[]),
+			LogNotice::Rejected(_) => (&translation.log_rejected, &[]),
+			LogNotice::Failed { .. } => (&translation.log_failed, &[LogAction::Approve, LogAction::Ban]),
+			LogNotice::Released { .. } => (&translation.log_released, &[LogAction::Ban]),
+			LogNotice::Banned(_) => (&translation.log_banned, &[]),
+		};
+
+		let (actor, detail) = match notice {
+			LogNotice::Approved(actor) | LogNotice::Rejected(actor) | LogNotice::Banned(actor) => {
+				(Some(actor), None)
+			},
+			LogNotice::Verified { credential_type } => (None, credential_type),
+			LogNotice::Failed { reason } | LogNotice::Released { reason } => (None, Some(reason)),
+			LogNotice::Joined | LogNotice::TimedOut => (None, None),
+		};
+		let detail = html::escape(detail.unwrap_or("-"));
+
+		let text = html::escape(template)
+			.replace("{USER}", &mention(user_id, user))
+			.replace("{ACTOR}", &html::escape(&actor.map(|actor| actor.to_string()).unwrap_or_default()))
+			.replace("{CREDENTIAL}", &detail)
+			.replace("{REASON}", &detail);
+
+		let buttons = actions
+			.iter()
+			.map(|action| {
+				let label = match action {
+					LogAction::Approve => &translation.log_approve_button,
+					LogAction::Ban => &translation.log_ban_button,
+				};
+				InlineKeyboardButton::callback(label, action.callback_data(chat_id, user_id))
+			})
+			.collect::<Vec<_>>();
+
+		self.post(chat_id, log_chat_id, settings, text, buttons);
+	}
+
+	/// Avisa de los permisos que le faltan al bot, o de que ya los tiene todos si `missing` está vacío
+	pub fn permissions_changed(&self, chat_id: ChatId, missing: &[Permission]) {
+		let settings = self.config.groups_config.get(chat_id);
+		let Some(log_chat_id) = settings.log_chat_id else {
+			return;
+		};
+
+		let translation = self.i18n.get(self.i18n.resolve([settings.language.as_deref()]));
+		let text = if missing.is_empty() {
+			html::escape(&translation.log_permissions_restored)
+		} else {
+			html::escape(&translation.log_missing_permissions).replace(
+				"{PERMISSIONS}",
+				&html::escape(&Permission::describe(missing, translation)),
+			)
+		};
+
+		self.post(chat_id, log_chat_id, settings, text, Vec::new());
+	}
+
+	/// Publica el texto en segundo plano, encabezado por el nombre del grupo
+	fn post(
+		&self,
+		chat_id: ChatId,
+		log_chat_id: ChatId,
+		settings: GroupSettings,
+		text: String,
+		buttons: Vec<InlineKeyboardButton>,
+	) {
+		let bot = self.bot.clone();
+		tokio::spawn(async move {
+			let title = match settings.chat_name {
+				Some(title) => title,
+				None => bot
+					.get_chat(chat_id)
+					.await
+					.ok()
+					.and_then(|chat| chat.title().map(str::to_string))
+					.unwrap_or_else(|| chat_id.to_string()),
+			};
+
+			let mut request = bot
+				.send_message(log_chat_id, format!("<b>{}</b>\n{text}", html::escape(&title)))
+				.parse_mode(ParseMode::Html)
+				.disable_web_page_preview(true);
+			if !buttons.is_empty() {
+				request = request.reply_markup(InlineKeyboardMarkup::new([buttons]));
+			}
+
+			if let Err(e) = request.await {
+				log::warn!("Failed to post to the log channel {log_chat_id} of {chat_id}: {e}");
+			}
+		});
+	}
+}
+
+/// Mención HTML del usuario, con su nombre si se conoce
+fn mention(user_id: UserId, user: Option<&User>) -> String {
+	let name = user.map_or_else(|| user_id.to_string(), |user| user.full_name());
+
+	format!("<a href=\"tg://user?id={user_id}\">{}</a>", html::escape(&name))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_own_callback_data() {
+		for action in [LogAction::Approve, LogAction::Ban] {
+			let data = action.callback_data(ChatId(-1001234567890), UserId(42));
+			assert_eq!(
+				LogAction::parse_callback(&data),
+				Some((action, ChatId(-1001234567890), UserId(42)))
+			);
+		}
+	}
+
+	#[test]
+	fn rejects_foreign_or_malformed_callback_data() {
+		for data in [
+			"welcome:approve:42",
+			"log:kick:-100:42",
+			"log:ban:-100",
+			"log:ban:group:42",
+			"log:ban:-100:-42",
+			"",
+		] {
+			assert_eq!(LogAction::parse_callback(data), None, "{data}");
+		}
+	}
+}
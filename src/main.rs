@@ -4,19 +4,35 @@ use std::sync::Arc;
 use teloxide::{
 	requests::Requester,
 	types::{ChatId, UserId},
-	Bot,
 };
 
 use crate::{
+	analytics::Analytics,
+	audit::AuditLog,
 	bot::{JoinRequest, JoinRequests},
 	config::AppConfig,
+	events::Events,
 	i18n::I18n, // AÑADIR
+	jobs::Jobs,
+	log_channel::LogChannel,
+	metrics::Metrics,
+	permissions::PermissionMonitor,
+	webhooks::Webhooks,
 };
 
+mod analytics;
+mod audit;
 mod bot;
 mod config;
+mod events;
 mod server;
+mod webhooks;
 mod i18n; // AÑADIR
+mod jobs;
+mod log_channel;
+mod messages;
+mod metrics;
+mod permissions;
 
 #[tokio::main]
 async fn main() {
@@ -26,11 +42,38 @@ async fn main() {
 	let config = AppConfig::try_read().expect("Failed to read config");
 	let join_requests: JoinRequests = Arc::new(DashMap::<(ChatId, UserId), JoinRequest>::new());
 	let i18n = Arc::new(I18n::new()); // AÑADIR
-	let bot = Bot::new(&config.bot_token);
+	let bot = bot::build(&config);
 	let bot_data = bot.get_me().await.expect("Failed to get bot account");
+
+	let events = Events {
+		audit: Arc::new(AuditLog::open(&config.audit)),
+		metrics: Arc::new(Metrics::new()),
+		analytics: Arc::new(Analytics::new(&config)),
+		webhooks: Arc::new(Webhooks::new(config.clone())),
+		log_channel: Arc::new(LogChannel::new(bot.clone(), config.clone(), i18n.clone())),
+	};
+	tokio::spawn(events.audit.clone().run_retention());
+	let jobs = Jobs::start(bot.clone());
+
+	let permissions = Arc::new(PermissionMonitor::new(
+		bot.clone(),
+		bot_data.id,
+		config.clone(),
+		join_requests.clone(),
+		events.clone(),
+	));
+	tokio::spawn(permissions.clone().run());
 	
 	tokio::join!(
-		bot::start(bot.clone(), config.clone(), join_requests.clone(), i18n.clone()), // MODIFICAR
-		server::start(bot, config, bot_data.user, join_requests, i18n) // MODIFICAR
+		bot::start(
+			bot.clone(),
+			bot_data.clone(),
+			config.clone(),
+			join_requests.clone(),
+			i18n.clone(),
+			events.clone(),
+			jobs
+		),
+		server::start(bot, config, bot_data.user, join_requests, i18n, events, permissions)
 	);
 }
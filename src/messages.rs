@@ -0,0 +1,359 @@
+use humantime_serde::re::humantime;
+use minijinja::{context, Environment, Error, UndefinedBehavior, Value};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use teloxide::{
+	types::{ParseMode, User},
+	utils::{html, markdown},
+};
+use url::Url;
+
+/// Variables disponibles en `new_user_template`
+pub const WELCOME_VARIABLES: &[&str] = &[
+	"mention",
+	"users",
+	"first_name",
+	"username",
+	"user_id",
+	"verify_url",
+	"chat_name",
+	"timeout",
+	"time_left",
+	"member_count",
+	"rules_url",
+];
+
+/// Funciones de minijinja que también aparecen como variables sin declarar
+const BUILTIN_FUNCTIONS: &[&str] = &["range", "dict", "namespace"];
+
+/// Etiquetas de las plantillas anteriores y la variable equivalente
+const LEGACY_PLACEHOLDERS: &[(&str, &str)] = &[
+	("{TAGUSER}", "{{ mention }}"),
+	("{CHATNAME}", "{{ chat_name }}"),
+	("{TIMELEFT}", "{{ time_left }}"),
+];
+
+/// Formato de la plantilla, que decide el `parse_mode` del mensaje y cómo se escapan las variables
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateFormat {
+	#[default]
+	Html,
+	MarkdownV2,
+}
+
+impl TemplateFormat {
+	pub const fn parse_mode(self) -> ParseMode {
+		match self {
+			Self::Html => ParseMode::Html,
+			Self::MarkdownV2 => ParseMode::MarkdownV2,
+		}
+	}
+
+	fn escape(self, text: &str) -> String {
+		match self {
+			Self::Html => html::escape(text),
+			Self::MarkdownV2 => markdown::escape(text),
+		}
+	}
+
+	/// `@usuario` si tiene nombre de usuario; si no, un enlace `tg://user` con su nombre
+	fn mention(self, user: &User) -> String {
+		let user_id = user.id.0 as i64;
+
+		match (self, &user.username) {
+			(Self::Html, Some(username)) => format!("@{username}"),
+			(Self::Html, None) => html::user_mention(user_id, &user.full_name()),
+			(Self::MarkdownV2, Some(username)) => markdown::escape(&format!("@{username}")),
+			(Self::MarkdownV2, None) => {
+				markdown::user_mention(user_id, &markdown::escape(&user.full_name()))
+			},
+		}
+	}
+}
+
+/// Datos con los que se genera el mensaje de bienvenida de un lote de usuarios
+#[derive(Debug, Clone)]
+pub struct WelcomeContext {
+	/// Usuarios del lote, cada uno con su enlace de verificación
+	pub users: Vec<(User, Url)>,
+	pub chat_name: String,
+	/// Plazo total de verificación (`ban_after`)
+	pub timeout: Duration,
+	pub time_left: Duration,
+	pub member_count: Option<u32>,
+	pub rules_url: Option<Url>,
+}
+
+/// Plantilla de bienvenida (sintaxis de minijinja), con las etiquetas `{TAGUSER}`,
+/// `{CHATNAME}` y `{TIMELEFT}` de versiones anteriores todavía admitidas
+#[derive(Debug, Clone)]
+pub struct WelcomeTemplate {
+	source: String,
+	format: TemplateFormat,
+}
+
+impl WelcomeTemplate {
+	pub fn new(template: &str, format: TemplateFormat) -> Self {
+		let source = LEGACY_PLACEHOLDERS
+			.iter()
+			.fold(template.to_string(), |source, (legacy, variable)| {
+				source.replace(legacy, variable)
+			});
+
+		Self { source, format }
+	}
+
+	pub const fn format(&self) -> TemplateFormat {
+		self.format
+	}
+
+	/// Comprueba la sintaxis y que solo se usen variables conocidas
+	pub fn validate(&self) -> Result<(), String> {
+		let env = self.environment(Some(self.format));
+		let template = env.template_from_str(&self.source).map_err(|e| e.to_string())?;
+
+		let mut unknown = template
+			.undeclared_variables(false)
+			.into_iter()
+			.filter(|name| {
+				!WELCOME_VARIABLES.contains(&name.as_str())
+					&& !BUILTIN_FUNCTIONS.contains(&name.as_str())
+			})
+			.collect::<Vec<_>>();
+		if unknown.is_empty() {
+			return Ok(());
+		}
+
+		unknown.sort();
+		Err(format!(
+			"unknown variables {} (available: {})",
+			unknown.join(", "),
+			WELCOME_VARIABLES.join(", ")
+		))
+	}
+
+	/// Determina si la plantilla usa la variable, para no calcular las que no hacen falta
+	pub fn uses(&self, variable: &str) -> bool {
+		self.environment(Some(self.format))
+			.template_from_str(&self.source)
+			.is_ok_and(|template| template.undeclared_variables(false).contains(variable))
+	}
+
+	/// Genera el texto, escapando cada variable según el formato de la plantilla
+	pub fn render(&self, ctx: &WelcomeContext) -> Result<String, Error> {
+		self.render_as(ctx, Some(self.format))
+	}
+
+	/// Genera el texto sin formato: las variables van tal cual y las menciones son texto
+	pub fn render_plain(&self, ctx: &WelcomeContext) -> Result<String, Error> {
+		self.render_as(ctx, None)
+	}
+
+	fn render_as(
+		&self,
+		ctx: &WelcomeContext,
+		format: Option<TemplateFormat>,
+	) -> Result<String, Error> {
+		let mention = |user: &User| match format {
+			Some(format) => format.mention(user),
+			None => user
+				.username
+				.as_ref()
+				.map_or_else(|| user.full_name(), |username| format!("@{username}")),
+		};
+		let users = ctx
+			.users
+			.iter()
+			.map(|(user, verify_url)| {
+				context! {
+					first_name => &user.first_name,
+					username => &user.username,
+					user_id => user.id.0,
+					mention => Value::from_safe_string(mention(user)),
+					verify_url => verify_url.as_str(),
+				}
+			})
+			.collect::<Vec<_>>();
+		let mentions = ctx
+			.users
+			.iter()
+			.map(|(user, _)| mention(user))
+			.collect::<Vec<_>>()
+			.join(", ");
+		let first = ctx.users.first().map(|(user, verify_url)| (user, verify_url.as_str()));
+
+		self.environment(format).render_str(
+			&self.source,
+			context! {
+				mention => Value::from_safe_string(mentions),
+				first_name => first.map(|(user, _)| &user.first_name),
+				username => first.and_then(|(user, _)| user.username.as_ref()),
+				user_id => first.map(|(user, _)| user.id.0),
+				verify_url => first.map(|(_, verify_url)| verify_url),
+				users,
+				chat_name => &ctx.chat_name,
+				timeout => format_seconds(ctx.timeout),
+				time_left => format_seconds(ctx.time_left),
+				member_count => ctx.member_count,
+				rules_url => ctx.rules_url.as_ref().map(Url::as_str),
+			},
+		)
+	}
+
+	/// Entorno de minijinja que escapa las variables según `format`, o no las escapa si es `None`
+	fn environment(&self, format: Option<TemplateFormat>) -> Environment<'static> {
+		let mut env = Environment::new();
+		env.set_undefined_behavior(UndefinedBehavior::Strict);
+		env.set_formatter(move |out, _state, value| {
+			// Las variables opcionales sin valor (sin `username`, sin `rules_url`...) no se muestran
+			if value.is_none() || value.is_undefined() {
+				return Ok(());
+			}
+
+			let text = value.to_string();
+			match format {
+				Some(format) if !value.is_safe() => out.write_str(&format.escape(&text))?,
+				_ => out.write_str(&text)?,
+			}
+			Ok(())
+		});
+
+		env
+	}
+}
+
+/// Duración redondeada a segundos, como "4m 59s"
+fn format_seconds(duration: Duration) -> String {
+	humantime::format_duration(Duration::from_secs(duration.as_secs())).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use teloxide::types::UserId;
+
+	fn user(first_name: &str, username: Option<&str>) -> User {
+		User {
+			id: UserId(42),
+			is_bot: false,
+			first_name: first_name.to_string(),
+			last_name: None,
+			username: username.map(str::to_string),
+			language_code: None,
+			is_premium: false,
+			added_to_attachment_menu: false,
+		}
+	}
+
+	fn context(user: User) -> WelcomeContext {
+		WelcomeContext {
+			users: vec![(user, Url::parse("https://example.com/verify/1/42").unwrap())],
+			chat_name: "Rust & <Friends>".to_string(),
+			timeout: Duration::from_secs(300),
+			time_left: Duration::from_millis(299_500),
+			member_count: None,
+			rules_url: None,
+		}
+	}
+
+	fn render(template: &str, format: TemplateFormat, user: User) -> String {
+		WelcomeTemplate::new(template, format)
+			.render(&context(user))
+			.unwrap()
+	}
+
+	#[test]
+	fn escapes_variables_as_html() {
+		let text = render(
+			"<b>{{ first_name }}</b> · {{ chat_name }}",
+			TemplateFormat::Html,
+			user("<Ada>", None),
+		);
+		assert_eq!(text, "<b>&lt;Ada&gt;</b> · Rust &amp; &lt;Friends&gt;");
+	}
+
+	#[test]
+	fn escapes_variables_as_markdown_v2() {
+		let text = render(
+			"*{{ first_name }}*",
+			TemplateFormat::MarkdownV2,
+			user("Ada_1.", None),
+		);
+		assert_eq!(text, r"*Ada\_1\.*");
+	}
+
+	#[test]
+	fn mentions_by_username_or_link() {
+		let html = TemplateFormat::Html;
+		assert_eq!(
+			render("{{ mention }}", html, user("Ada", Some("ada"))),
+			"@ada"
+		);
+		assert_eq!(
+			render("{{ mention }}", html, user("<Ada>", None)),
+			r#"<a href="tg://user?id=42">&lt;Ada&gt;</a>"#
+		);
+
+		let markdown = TemplateFormat::MarkdownV2;
+		assert_eq!(
+			render("{{ mention }}", markdown, user("Ada", Some("ada_l"))),
+			r"@ada\_l"
+		);
+		assert_eq!(
+			render("{{ mention }}", markdown, user("Ada.", None)),
+			r"[Ada\.](tg://user?id=42)"
+		);
+	}
+
+	#[test]
+	fn rewrites_legacy_placeholders() {
+		let text = render(
+			"{TAGUSER} · {CHATNAME} · {TIMELEFT}",
+			TemplateFormat::Html,
+			user("Ada", Some("ada")),
+		);
+		assert_eq!(text, "@ada · Rust &amp; &lt;Friends&gt; · 4m 59s");
+
+		let template = WelcomeTemplate::new("{TIMELEFT}", TemplateFormat::Html);
+		assert!(template.uses("time_left"));
+		assert!(!template.uses("member_count"));
+	}
+
+	#[test]
+	fn hides_missing_optional_values() {
+		let text = render(
+			"[{{ username }}][{{ rules_url }}][{{ member_count }}][{{ users[0].username }}]",
+			TemplateFormat::Html,
+			user("Ada", None),
+		);
+		assert_eq!(text, "[][][][]");
+	}
+
+	#[test]
+	fn renders_plain_text_without_escaping() {
+		let text = WelcomeTemplate::new("{TAGUSER} · {CHATNAME}", TemplateFormat::MarkdownV2)
+			.render_plain(&context(user("Ada.", None)))
+			.unwrap();
+		assert_eq!(text, "Ada. · Rust & <Friends>");
+	}
+
+	#[test]
+	fn validates_known_variables() {
+		let template = WelcomeTemplate::new(
+			"{% for user in users %}{{ user.mention }} {% endfor %}{{ rules_url }} {TAGUSER}",
+			TemplateFormat::Html,
+		);
+		assert_eq!(template.validate(), Ok(()));
+
+		let template = WelcomeTemplate::new("{{ nickname }} {{ chat }}", TemplateFormat::Html);
+		let err = template.validate().unwrap_err();
+		assert!(err.starts_with("unknown variables chat, nickname"), "{err}");
+	}
+
+	#[test]
+	fn rejects_invalid_syntax() {
+		let template = WelcomeTemplate::new("{{ mention", TemplateFormat::Html);
+		assert!(template.validate().is_err());
+	}
+}
@@ -0,0 +1,120 @@
+use prometheus::{
+	Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry,
+	TextEncoder,
+};
+
+use crate::bot::JoinRequests;
+
+/// Métricas de Prometheus expuestas en `/metrics`
+pub struct Metrics {
+	registry: Registry,
+	pub joins: IntCounter,
+	pub kicks: IntCounter,
+	pub timeouts: IntCounter,
+	pub pending_messages_deleted: IntCounter,
+	pub verifications_started: IntCounter,
+	pub verifications_succeeded: IntCounter,
+	pub verifications_failed: IntCounterVec,
+	pub developer_portal_latency: Histogram,
+	pending_join_requests: IntGaugeVec,
+	pub missing_permissions: IntGaugeVec,
+}
+
+impl Metrics {
+	pub fn new() -> Self {
+		let registry = Registry::new_custom(Some("world_id_telegram".to_string()), None)
+			.expect("valid metrics prefix");
+
+		let metrics = Self {
+			joins: IntCounter::new("joins_total", "Users that joined a group").unwrap(),
+			kicks: IntCounter::new("kicks_total", "Users removed from a group").unwrap(),
+			timeouts: IntCounter::new(
+				"timeouts_total",
+				"Users that didn't verify before the deadline",
+			)
+			.unwrap(),
+			pending_messages_deleted: IntCounter::new(
+				"pending_messages_deleted_total",
+				"Messages deleted because their sender hadn't verified yet",
+			)
+			.unwrap(),
+			verifications_started: IntCounter::new(
+				"verifications_started_total",
+				"Proofs submitted to the verify API",
+			)
+			.unwrap(),
+			verifications_succeeded: IntCounter::new(
+				"verifications_succeeded_total",
+				"Users verified with World ID",
+			)
+			.unwrap(),
+			verifications_failed: IntCounterVec::new(
+				Opts::new("verifications_failed_total", "Failed verifications by reason"),
+				&["reason"],
+			)
+			.unwrap(),
+			developer_portal_latency: Histogram::with_opts(HistogramOpts::new(
+				"developer_portal_request_duration_seconds",
+				"Latency of proof verification requests to the Developer Portal",
+			))
+			.unwrap(),
+			pending_join_requests: IntGaugeVec::new(
+				Opts::new("pending_join_requests", "Users waiting to verify, by chat"),
+				&["chat_id"],
+			)
+			.unwrap(),
+			missing_permissions: IntGaugeVec::new(
+				Opts::new(
+					"missing_permissions",
+					"Admin rights the bot lacks (1) or has (0), by chat",
+				),
+				&["chat_id", "permission"],
+			)
+			.unwrap(),
+			registry,
+		};
+
+		for collector in [
+			Box::new(metrics.joins.clone()) as Box<dyn prometheus::core::Collector>,
+			Box::new(metrics.kicks.clone()),
+			Box::new(metrics.timeouts.clone()),
+			Box::new(metrics.pending_messages_deleted.clone()),
+			Box::new(metrics.verifications_started.clone()),
+			Box::new(metrics.verifications_succeeded.clone()),
+			Box::new(metrics.verifications_failed.clone()),
+			Box::new(metrics.developer_portal_latency.clone()),
+			Box::new(metrics.pending_join_requests.clone()),
+			Box::new(metrics.missing_permissions.clone()),
+		] {
+			metrics
+				.registry
+				.register(collector)
+				.expect("metrics are only registered once");
+		}
+
+		metrics
+	}
+
+	/// Renderiza todas las métricas en el formato de texto de Prometheus
+	pub fn render(&self, join_requests: &JoinRequests) -> String {
+		self.pending_join_requests.reset();
+		for join_req in join_requests.iter().filter(|req| !req.is_verified) {
+			self.pending_join_requests
+				.with_label_values(&[&join_req.key().0.to_string()])
+				.inc();
+		}
+
+		let mut buffer = Vec::new();
+		TextEncoder::new()
+			.encode(&self.registry.gather(), &mut buffer)
+			.expect("text encoding can't fail");
+
+		String::from_utf8(buffer).expect("metrics are valid UTF-8")
+	}
+}
+
+impl Default for Metrics {
+	fn default() -> Self {
+		Self::new()
+	}
+}
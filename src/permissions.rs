@@ -0,0 +1,215 @@
+use dashmap::DashMap;
+use serde::Serialize;
+use std::{sync::Arc, time::SystemTime};
+use teloxide::{
+	requests::Requester,
+	types::{Administrator, ChatId, ChatMemberKind, UserId},
+	RequestError,
+};
+
+use crate::{
+	bot::{Bot, JoinRequests},
+	config::AppConfig, events::Events, i18n::Translation,
+};
+
+/// Derechos de administrador que el bot necesita en cada grupo
+/// (con los nombres de la API de Telegram)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Permission {
+	/// Silenciar a los usuarios pendientes y expulsarlos
+	#[serde(rename = "can_restrict_members")]
+	RestrictMembers,
+	/// Borrar la bienvenida, los mensajes de servicio y los de usuarios pendientes
+	#[serde(rename = "can_delete_messages")]
+	DeleteMessages,
+	#[serde(rename = "can_invite_users")]
+	InviteUsers,
+}
+
+impl Permission {
+	pub const ALL: [Self; 3] = [
+		Self::RestrictMembers,
+		Self::DeleteMessages,
+		Self::InviteUsers,
+	];
+
+	pub const fn as_str(&self) -> &'static str {
+		match self {
+			Self::RestrictMembers => "can_restrict_members",
+			Self::DeleteMessages => "can_delete_messages",
+			Self::InviteUsers => "can_invite_users",
+		}
+	}
+
+	fn is_granted(&self, member: &ChatMemberKind) -> bool {
+		let ChatMemberKind::Administrator(admin) = member else {
+			return member.is_owner();
+		};
+
+		let Administrator {
+			can_restrict_members,
+			can_delete_messages,
+			can_invite_users,
+			..
+		} = admin;
+		match self {
+			Self::RestrictMembers => *can_restrict_members,
+			Self::DeleteMessages => *can_delete_messages,
+			Self::InviteUsers => *can_invite_users,
+		}
+	}
+
+	/// Nombres de los permisos como aparecen en Telegram, separados por comas
+	pub fn describe(permissions: &[Self], translation: &Translation) -> String {
+		permissions
+			.iter()
+			.map(|permission| match permission {
+				Self::RestrictMembers => translation.permission_restrict_members.as_str(),
+				Self::DeleteMessages => translation.permission_delete_messages.as_str(),
+				Self::InviteUsers => translation.permission_invite_users.as_str(),
+			})
+			.collect::<Vec<_>>()
+			.join(", ")
+	}
+}
+
+/// Estado de los permisos del bot en un grupo, según la última comprobación
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionStatus {
+	pub chat_id: ChatId,
+	/// Si el bot sigue en el grupo; si no, no se comprueban los permisos
+	pub is_member: bool,
+	pub missing: Vec<Permission>,
+	#[serde(with = "humantime_serde")]
+	pub checked_at: SystemTime,
+}
+
+/// Derechos que le faltan al bot en el grupo; todos si no es administrador.
+/// `None` si el bot ya no está en el grupo
+pub async fn missing_permissions(
+	bot: &Bot,
+	chat_id: ChatId,
+	bot_id: UserId,
+) -> Result<Option<Vec<Permission>>, RequestError> {
+	let member = bot.get_chat_member(chat_id, bot_id).await?;
+	if !member.is_present() {
+		return Ok(None);
+	}
+
+	Ok(Some(
+		Permission::ALL
+			.into_iter()
+			.filter(|permission| !permission.is_granted(&member.kind))
+			.collect(),
+	))
+}
+
+/// Comprueba los permisos del bot en los grupos conocidos al iniciar y cada
+/// `permission_check_interval`, y avisa cuando cambian
+pub struct PermissionMonitor {
+	bot: Bot,
+	bot_id: UserId,
+	config: AppConfig,
+	join_requests: JoinRequests,
+	events: Events,
+	statuses: DashMap<ChatId, PermissionStatus>,
+}
+
+impl PermissionMonitor {
+	pub fn new(
+		bot: Bot,
+		bot_id: UserId,
+		config: AppConfig,
+		join_requests: JoinRequests,
+		events: Events,
+	) -> Self {
+		Self {
+			bot,
+			bot_id,
+			config,
+			join_requests,
+			events,
+			statuses: DashMap::new(),
+		}
+	}
+
+	/// Última comprobación de cada grupo
+	pub fn statuses(&self) -> Vec<PermissionStatus> {
+		let mut statuses = self
+			.statuses
+			.iter()
+			.map(|status| status.value().clone())
+			.collect::<Vec<_>>();
+
+		statuses.sort_by_key(|status| status.chat_id.0);
+		statuses
+	}
+
+	/// Comprueba los permisos en el grupo y avisa si cambiaron desde la última vez
+	pub async fn check(&self, chat_id: ChatId) -> Result<PermissionStatus, RequestError> {
+		let missing = missing_permissions(&self.bot, chat_id, self.bot_id).await?;
+		let status = PermissionStatus {
+			chat_id,
+			is_member: missing.is_some(),
+			missing: missing.unwrap_or_default(),
+			checked_at: SystemTime::now(),
+		};
+
+		let previous = self.statuses.insert(chat_id, status.clone());
+
+		// Se avisa de los permisos que faltan y, después, de cuando se recuperan;
+		// un grupo que ya estaba bien desde el inicio no genera avisos
+		let changed = match &previous {
+			Some(previous) => previous.missing != status.missing,
+			None => !status.missing.is_empty(),
+		};
+		if status.is_member && changed {
+			if !status.missing.is_empty() {
+				log::warn!(
+					"Missing admin rights in {chat_id}: {}",
+					status
+						.missing
+						.iter()
+						.map(Permission::as_str)
+						.collect::<Vec<_>>()
+						.join(", ")
+				);
+			}
+			self.events.permissions_changed(chat_id, &status.missing);
+		}
+
+		Ok(status)
+	}
+
+	/// Comprueba todos los grupos al iniciar y después periódicamente
+	pub async fn run(self: Arc<Self>) {
+		let interval = self.config.permission_check_interval;
+
+		loop {
+			for chat_id in self.known_chats() {
+				if let Err(e) = self.check(chat_id).await {
+					log::warn!("Failed to check the bot permissions in {chat_id}: {e}");
+				}
+			}
+
+			if interval.is_zero() {
+				break;
+			}
+			tokio::time::sleep(interval).await;
+		}
+	}
+
+	/// Grupos configurados, permitidos, con usuarios pendientes o con actividad registrada
+	fn known_chats(&self) -> Vec<ChatId> {
+		let groups_config = &self.config.groups_config;
+
+		let mut chat_ids = groups_config.chat_ids();
+		chat_ids.extend(&groups_config.allowed_group_ids);
+		chat_ids.extend(self.join_requests.iter().map(|req| req.key().0));
+		chat_ids.extend(self.events.audit.chat_ids());
+
+		chat_ids.sort_by_key(|chat_id| chat_id.0);
+		chat_ids.dedup();
+		chat_ids
+	}
+}
@@ -0,0 +1,302 @@
+use axum::{
+	extract::{Path, Query, Request},
+	http::{header, HeaderMap, StatusCode},
+	middleware::{self, Next},
+	response::{IntoResponse, Response},
+	routing::{get, post},
+	Extension, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use teloxide::{
+	types::{ChatId, UserId},
+};
+
+use crate::{
+	audit::{Actor, AuditAction, AuditEntry, AuditFilter},
+	bot::{on_verified, reject, Bot, JoinRequests},
+	config::{AppConfig, GroupSettings},
+	events::Events,
+	permissions::{PermissionMonitor, PermissionStatus},
+	webhooks::Delivery,
+};
+
+/// Rutas de `/admin/api`, todas protegidas por la `admin_api_key`
+pub fn router() -> Router {
+	Router::new()
+		.route("/chats", get(list_chats))
+		.route("/chats/:chat_id/pending", get(list_pending))
+		.route("/chats/:chat_id/pending/:user_id/approve", post(approve))
+		.route("/chats/:chat_id/pending/:user_id/reject", post(reject_user))
+		.route("/chats/:chat_id/settings", get(get_settings).put(update_settings))
+		.route("/chats/:chat_id/verified", get(list_verified))
+		.route("/chats/:chat_id/permissions", get(check_permissions))
+		.route("/permissions", get(list_permissions))
+		.route("/audit", get(export_audit))
+		.route("/webhooks/deliveries", get(list_deliveries))
+		.layer(middleware::from_fn(require_api_key))
+}
+
+/// Exige la `admin_api_key` en las rutas de la API
+async fn require_api_key(
+	Extension(config): Extension<AppConfig>,
+	headers: HeaderMap,
+	req: Request,
+	next: Next,
+) -> Result<Response, StatusCode> {
+	check_token(&headers, config.admin_api_key.as_deref())?;
+
+	Ok(next.run(req).await)
+}
+
+/// Comprueba el token de `Authorization: Bearer <token>` o `X-Api-Key: <token>`; sin token
+/// configurado la ruta no existe
+pub(super) fn check_token(headers: &HeaderMap, expected: Option<&str>) -> Result<(), StatusCode> {
+	let expected = expected.ok_or(StatusCode::NOT_FOUND)?;
+	let provided = headers
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "))
+		.or_else(|| headers.get("X-Api-Key").and_then(|value| value.to_str().ok()))
+		.ok_or(StatusCode::UNAUTHORIZED)?;
+
+	// Se comparan los hashes para no filtrar la longitud ni el prefijo de la clave por el tiempo
+	if Sha256::digest(provided) != Sha256::digest(expected) {
+		return Err(StatusCode::UNAUTHORIZED);
+	}
+
+	Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct ChatSummary {
+	chat_id: ChatId,
+	pending: usize,
+	configured: bool,
+}
+
+async fn list_chats(
+	Extension(config): Extension<AppConfig>,
+	Extension(join_reqs): Extension<JoinRequests>,
+) -> Json<Vec<ChatSummary>> {
+	let configured = config.groups_config.chat_ids();
+
+	let mut chat_ids = configured.clone();
+	chat_ids.extend(join_reqs.iter().map(|req| req.key().0));
+	chat_ids.sort_by_key(|chat_id| chat_id.0);
+	chat_ids.dedup();
+
+	Json(
+		chat_ids
+			.into_iter()
+			.map(|chat_id| ChatSummary {
+				chat_id,
+				configured: configured.contains(&chat_id),
+				pending: join_reqs
+					.iter()
+					.filter(|req| req.key().0 == chat_id && !req.is_verified)
+					.count(),
+			})
+			.collect(),
+	)
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct PendingRequest {
+	user_id: UserId,
+	name: String,
+	username: Option<String>,
+	lang: String,
+	is_verified: bool,
+	expires_in_secs: u64,
+	/// Errores de Telegram al gestionar la solicitud, como `<paso>: <error>`
+	failures: Vec<String>,
+}
+
+/// Solicitudes de unión de un grupo, compartidas con el panel
+pub(super) fn pending_requests(join_reqs: &JoinRequests, chat_id: ChatId) -> Vec<PendingRequest> {
+	let now = Instant::now();
+
+	join_reqs
+		.iter()
+		.filter(|req| req.key().0 == chat_id)
+		.map(|req| PendingRequest {
+			user_id: req.key().1,
+			lang: req.lang.clone(),
+			is_verified: req.is_verified,
+			name: req.user.full_name(),
+			username: req.user.username.clone(),
+			expires_in_secs: req.expires_at.saturating_duration_since(now).as_secs(),
+			failures: req.failures.clone(),
+		})
+		.collect()
+}
+
+async fn list_pending(
+	Path(chat_id): Path<ChatId>,
+	Extension(join_reqs): Extension<JoinRequests>,
+) -> Json<Vec<PendingRequest>> {
+	Json(pending_requests(&join_reqs, chat_id))
+}
+
+async fn approve(
+	Path((chat_id, user_id)): Path<(ChatId, UserId)>,
+	Extension(bot): Extension<Bot>,
+	Extension(join_reqs): Extension<JoinRequests>,
+	Extension(events): Extension<Events>,
+) -> Result<StatusCode, StatusCode> {
+	if !join_reqs.contains_key(&(chat_id, user_id)) {
+		return Err(StatusCode::NOT_FOUND);
+	}
+
+	on_verified(bot, chat_id, user_id, join_reqs, events, Actor::AdminApi)
+		.await
+		.map_err(|e| {
+			log::error!("Failed to approve {user_id} in {chat_id}: {e:?}");
+			StatusCode::BAD_GATEWAY
+		})?;
+
+	Ok(StatusCode::NO_CONTENT)
+}
+
+async fn reject_user(
+	Path((chat_id, user_id)): Path<(ChatId, UserId)>,
+	Extension(bot): Extension<Bot>,
+	Extension(join_reqs): Extension<JoinRequests>,
+	Extension(events): Extension<Events>,
+) -> Result<StatusCode, StatusCode> {
+	if !join_reqs.contains_key(&(chat_id, user_id)) {
+		return Err(StatusCode::NOT_FOUND);
+	}
+
+	reject(&bot, chat_id, user_id, &join_reqs, &events, Actor::AdminApi)
+		.await
+		.map_err(|e| {
+			log::error!("Failed to reject {user_id} in {chat_id}: {e:?}");
+			StatusCode::BAD_GATEWAY
+		})?;
+
+	Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_settings(
+	Path(chat_id): Path<ChatId>,
+	Extension(config): Extension<AppConfig>,
+) -> Json<GroupSettings> {
+	Json(config.groups_config.get(chat_id))
+}
+
+/// Reemplaza la configuración del grupo en memoria, hasta que se reinicie el bot.
+/// Los webhooks sin secreto conservan el guardado para la misma URL, y una configuración
+/// inválida (plantilla, webhooks sin secreto...) se rechaza con `422`
+async fn update_settings(
+	Path(chat_id): Path<ChatId>,
+	Extension(config): Extension<AppConfig>,
+	Json(mut settings): Json<GroupSettings>,
+) -> Result<Json<GroupSettings>, (StatusCode, String)> {
+	// GET /settings nunca devuelve los secretos, así que reenviarla tal cual debe conservarlos
+	let current = config.groups_config.get(chat_id);
+	for webhook in &mut settings.webhooks {
+		if webhook.secret.is_none() {
+			webhook.secret = current
+				.webhooks
+				.iter()
+				.find(|stored| stored.url == webhook.url)
+				.and_then(|stored| stored.secret.clone());
+		}
+	}
+
+	settings
+		.validate()
+		.map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+	config.groups_config.set(chat_id, settings);
+
+	Ok(Json(config.groups_config.get(chat_id)))
+}
+
+/// Permisos de la última comprobación periódica en cada grupo conocido
+async fn list_permissions(
+	Extension(permissions): Extension<Arc<PermissionMonitor>>,
+) -> Json<Vec<PermissionStatus>> {
+	Json(permissions.statuses())
+}
+
+/// Comprueba en el momento los permisos del bot en el grupo
+async fn check_permissions(
+	Path(chat_id): Path<ChatId>,
+	Extension(permissions): Extension<Arc<PermissionMonitor>>,
+) -> Result<Json<PermissionStatus>, StatusCode> {
+	permissions.check(chat_id).await.map(Json).map_err(|e| {
+		log::error!("Failed to check the bot permissions in {chat_id}: {e:?}");
+		StatusCode::BAD_GATEWAY
+	})
+}
+
+async fn list_verified(
+	Path(chat_id): Path<ChatId>,
+	Extension(events): Extension<Events>,
+) -> Json<Vec<AuditEntry>> {
+	Json(
+		events
+			.audit
+			.query(&AuditFilter::chat(chat_id))
+			.into_iter()
+			.filter(|entry| matches!(entry.action, AuditAction::Verified | AuditAction::Approved))
+			.collect(),
+	)
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditQuery {
+	chat_id: Option<ChatId>,
+	user_id: Option<UserId>,
+	action: Option<AuditAction>,
+	/// Solo entradas más recientes que esta antigüedad, ej. `24h`
+	#[serde(default, with = "humantime_serde")]
+	since: Option<Duration>,
+	limit: Option<usize>,
+	#[serde(default)]
+	format: ExportFormat,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+	#[default]
+	Json,
+	Jsonl,
+}
+
+async fn export_audit(
+	Query(query): Query<AuditQuery>,
+	Extension(events): Extension<Events>,
+) -> Response {
+	let entries = events.audit.query(&AuditFilter {
+		chat_id: query.chat_id,
+		user_id: query.user_id,
+		action: query.action,
+		since: query.since,
+		limit: query.limit,
+	});
+
+	match query.format {
+		ExportFormat::Json => Json(entries).into_response(),
+		ExportFormat::Jsonl => {
+			let body = entries
+				.iter()
+				.filter_map(|entry| serde_json::to_string(entry).ok())
+				.map(|line| line + "\n")
+				.collect::<String>();
+
+			([(header::CONTENT_TYPE, "application/jsonl")], body).into_response()
+		},
+	}
+}
+
+async fn list_deliveries(Extension(events): Extension<Events>) -> Json<Vec<Delivery>> {
+	Json(events.webhooks.deliveries())
+}
@@ -0,0 +1,78 @@
+use axum::{
+	http::{header, StatusCode},
+	response::IntoResponse,
+	Extension,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Serialize;
+use sha2::{Digest, Sha384};
+use std::{fs, sync::Arc};
+
+use crate::config::IdKitConfig;
+
+/// Origen desde el que la página de verificación carga IDKit
+#[derive(Debug, Serialize)]
+pub struct IdKitAsset {
+	pub src: String,
+	pub integrity: Option<String>,
+	#[serde(skip)]
+	bundle: Option<Vec<u8>>,
+}
+
+impl IdKitAsset {
+	/// Lee el bundle local o usa la URL configurada. La URL solo se acepta con `integrity`, y sin
+	/// bundle falla al iniciar en vez de cargar una copia sin fijar
+	pub fn load(config: &IdKitConfig) -> Result<Arc<Self>, String> {
+		if let Some(url) = &config.url {
+			let Some(integrity) = &config.integrity else {
+				return Err(format!("idkit.url {url} requires idkit.integrity"));
+			};
+
+			return Ok(Arc::new(Self {
+				bundle: None,
+				src: url.to_string(),
+				integrity: Some(integrity.clone()),
+			}));
+		}
+
+		let bundle = fs::read(&config.bundle_path).map_err(|e| {
+			format!(
+				"Failed to read the IDKit bundle at {}: {e} (or set idkit.url and idkit.integrity)",
+				config.bundle_path.display()
+			)
+		})?;
+
+		// Sin `idkit.integrity` el hash sale del propio archivo y no fija su contenido
+		let integrity = format!("sha384-{}", STANDARD.encode(Sha384::digest(&bundle)));
+		match &config.integrity {
+			Some(expected) if *expected != integrity => {
+				return Err(format!(
+					"IDKit bundle at {} doesn't match the configured integrity (got {integrity})",
+					config.bundle_path.display()
+				));
+			},
+			Some(_) => {},
+			None => log::warn!("idkit.integrity isn't set, the IDKit bundle is served unpinned"),
+		}
+
+		Ok(Arc::new(Self {
+			bundle: Some(bundle),
+			integrity: Some(integrity),
+			src: "/static/idkit.js".to_string(),
+		}))
+	}
+}
+
+pub async fn idkit_bundle(Extension(idkit): Extension<Arc<IdKitAsset>>) -> impl IntoResponse {
+	let Some(bundle) = idkit.bundle.clone() else {
+		return Err(StatusCode::NOT_FOUND);
+	};
+
+	Ok((
+		[
+			(header::CONTENT_TYPE, "application/javascript"),
+			(header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+		],
+		bundle,
+	))
+}
@@ -0,0 +1,387 @@
+use axum::{
+	async_trait,
+	extract::{FromRequestParts, Path, Query},
+	http::{header, request::Parts, StatusCode},
+	response::{Html, IntoResponse, Redirect, Response},
+	routing::{get, post},
+	Extension, Form, Router,
+};
+use humantime_serde::re::humantime;
+use minijinja::context;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use teloxide::{
+	requests::Requester,
+	types::{ChatId, User, UserId},
+};
+
+use super::{admin::pending_requests, login, signature, templates::Templates};
+use crate::{
+	audit::{Actor, AuditAction, AuditEntry},
+	bot::{on_verified, reject, Bot, JoinRequests},
+	config::AppConfig,
+	events::Events,
+	i18n::I18n,
+};
+
+const SESSION_COOKIE: &str = "admin_session";
+const SESSION_TTL: Duration = Duration::from_secs(60 * 60 * 12);
+
+/// Entradas del registro que se muestran en cada página
+const RECENT_ENTRIES: usize = 50;
+
+/// Credenciales que se ofrecen en el formulario de configuración
+const CREDENTIAL_TYPES: &[&str] = &["orb", "phone", "device"];
+
+/// Panel en `/admin`, generado en el servidor, para los usuarios de `admin_ids`
+pub fn router() -> Router {
+	Router::new()
+		.route("/", get(index))
+		.route("/login", get(login_page))
+		.route("/auth", get(auth))
+		.route("/logout", post(logout))
+		.route("/chats/:chat_id", get(chat_page))
+		.route("/chats/:chat_id/settings", post(update_settings))
+		.route("/chats/:chat_id/pending/:user_id/approve", post(approve))
+		.route("/chats/:chat_id/pending/:user_id/reject", post(reject_user))
+}
+
+/// Administrador con sesión iniciada, leído de la cookie de sesión firmada
+struct AdminSession {
+	user_id: UserId,
+}
+
+impl AdminSession {
+	/// Solo los administradores de `admin_ids` del grupo pueden verlo o gestionarlo
+	fn authorize(&self, config: &AppConfig, chat_id: ChatId) -> Result<(), StatusCode> {
+		if config.groups_config.is_admin(chat_id, self.user_id) {
+			Ok(())
+		} else {
+			Err(StatusCode::FORBIDDEN)
+		}
+	}
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AdminSession {
+	type Rejection = Redirect;
+
+	async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+		let config = parts
+			.extensions
+			.get::<AppConfig>()
+			.expect("AppConfig extension is always set");
+
+		parts
+			.headers
+			.get_all(header::COOKIE)
+			.iter()
+			.filter_map(|value| value.to_str().ok())
+			.flat_map(|value| value.split(';'))
+			.filter_map(|cookie| cookie.trim().strip_prefix(&format!("{SESSION_COOKIE}=")))
+			.find_map(|session| login::verify_session(session, &config.bot_token))
+			.filter(|user_id| config.groups_config.is_admin_anywhere(*user_id))
+			.map(|user_id| Self { user_id })
+			.ok_or(Redirect::to("/admin/login"))
+	}
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginQuery {
+	error: Option<String>,
+}
+
+async fn login_page(
+	Query(query): Query<LoginQuery>,
+	Extension(config): Extension<AppConfig>,
+	Extension(me): Extension<User>,
+	Extension(templates): Extension<Templates>,
+) -> Result<Html<String>, StatusCode> {
+	render(
+		&templates,
+		"admin/login.html",
+		context! {
+			error => query.error,
+			bot_username => me.username,
+			auth_url => config.app_url.join("/admin/auth").map(|url| url.to_string()).ok(),
+		},
+	)
+}
+
+/// Destino de la redirección del Login Widget de Telegram
+async fn auth(
+	Query(fields): Query<Vec<(String, String)>>,
+	Extension(config): Extension<AppConfig>,
+) -> Response {
+	let user_id = match login::validate_login(&fields, &config.bot_token, signature::unix_now()) {
+		Ok(user_id) => user_id,
+		Err(e) => {
+			log::warn!("Rejected dashboard login: {e}");
+			return Redirect::to("/admin/login?error=invalid").into_response();
+		},
+	};
+
+	if !config.groups_config.is_admin_anywhere(user_id) {
+		log::warn!("User {user_id} tried to log into the dashboard without being an admin");
+		return Redirect::to("/admin/login?error=unauthorized").into_response();
+	}
+
+	let secure = if config.app_url.scheme() == "https" { "; Secure" } else { "" };
+	let cookie = format!(
+		"{SESSION_COOKIE}={}; Path=/admin; Max-Age={}; HttpOnly; SameSite=Lax{secure}",
+		login::sign_session(user_id, SESSION_TTL, &config.bot_token),
+		SESSION_TTL.as_secs()
+	);
+
+	([(header::SET_COOKIE, cookie)], Redirect::to("/admin")).into_response()
+}
+
+async fn logout() -> impl IntoResponse {
+	(
+		[(header::SET_COOKIE, format!("{SESSION_COOKIE}=; Path=/admin; Max-Age=0"))],
+		Redirect::to("/admin/login"),
+	)
+}
+
+#[derive(Debug, Serialize)]
+struct ChatOverview {
+	chat_id: ChatId,
+	title: String,
+	/// Presencia del bot: `admin`, `member` o `absent`
+	bot_status: &'static str,
+	pending: usize,
+}
+
+async fn chat_overview(
+	bot: &Bot,
+	me: &User,
+	config: &AppConfig,
+	join_reqs: &JoinRequests,
+	chat_id: ChatId,
+) -> ChatOverview {
+	let title = match bot.get_chat(chat_id).await {
+		Ok(chat) => chat.title().map(str::to_string),
+		Err(_) => None,
+	};
+
+	let bot_status = match bot.get_chat_member(chat_id, me.id).await {
+		Ok(member) if member.kind.is_privileged() => "admin",
+		Ok(member) if member.kind.is_present() => "member",
+		_ => "absent",
+	};
+
+	ChatOverview {
+		chat_id,
+		bot_status,
+		title: title
+			.or(config.groups_config.get(chat_id).chat_name)
+			.unwrap_or_else(|| chat_id.to_string()),
+		pending: join_reqs
+			.iter()
+			.filter(|req| req.key().0 == chat_id && !req.is_verified)
+			.count(),
+	}
+}
+
+/// Grupos conocidos por el bot (configurados, permitidos, con solicitudes pendientes o en el
+/// registro) que administra `user_id`
+fn known_chats(
+	config: &AppConfig,
+	join_reqs: &JoinRequests,
+	events: &Events,
+	user_id: UserId,
+) -> Vec<ChatId> {
+	let mut chat_ids = config.groups_config.chat_ids();
+	chat_ids.extend(&config.groups_config.allowed_group_ids);
+	chat_ids.extend(join_reqs.iter().map(|req| req.key().0));
+	chat_ids.extend(events.audit.chat_ids());
+
+	chat_ids.sort_by_key(|chat_id| chat_id.0);
+	chat_ids.dedup();
+	chat_ids.retain(|chat_id| config.groups_config.is_admin(*chat_id, user_id));
+	chat_ids
+}
+
+/// Entradas más recientes primero, sin las de `joined`
+fn recent_activity(events: &Events, keep: impl Fn(&AuditEntry) -> bool) -> Vec<AuditEntry> {
+	events.audit.latest(RECENT_ENTRIES, |entry| {
+		entry.action != AuditAction::Joined && keep(entry)
+	})
+}
+
+async fn index(
+	session: AdminSession,
+	Extension(bot): Extension<Bot>,
+	Extension(me): Extension<User>,
+	Extension(config): Extension<AppConfig>,
+	Extension(join_reqs): Extension<JoinRequests>,
+	Extension(templates): Extension<Templates>,
+	Extension(events): Extension<Events>,
+) -> Result<Html<String>, StatusCode> {
+	let chat_ids = known_chats(&config, &join_reqs, &events, session.user_id);
+
+	let mut chats = Vec::with_capacity(chat_ids.len());
+	for chat_id in &chat_ids {
+		chats.push(chat_overview(&bot, &me, &config, &join_reqs, *chat_id).await);
+	}
+
+	let activity = recent_activity(&events, |entry| chat_ids.contains(&entry.chat_id));
+
+	render(
+		&templates,
+		"admin/index.html",
+		context! { chats, activity, user_id => session.user_id },
+	)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn chat_page(
+	session: AdminSession,
+	Path(chat_id): Path<ChatId>,
+	Extension(bot): Extension<Bot>,
+	Extension(me): Extension<User>,
+	Extension(config): Extension<AppConfig>,
+	Extension(join_reqs): Extension<JoinRequests>,
+	Extension(i18n): Extension<Arc<I18n>>,
+	Extension(templates): Extension<Templates>,
+	Extension(events): Extension<Events>,
+) -> Result<Html<String>, StatusCode> {
+	session.authorize(&config, chat_id)?;
+
+	let settings = config.groups_config.get(chat_id);
+	let pending = pending_requests(&join_reqs, chat_id);
+
+	render(
+		&templates,
+		"admin/chat.html",
+		context! {
+			pending,
+			chat => chat_overview(&bot, &me, &config, &join_reqs, chat_id).await,
+			activity => recent_activity(&events, |entry| entry.chat_id == chat_id),
+			ban_after => humantime::format_duration(settings.ban_after).to_string(),
+			settings,
+			languages => i18n.available_languages(),
+			credential_types => CREDENTIAL_TYPES,
+			user_id => session.user_id,
+		},
+	)
+}
+
+/// Aplica el formulario de configuración, en memoria hasta que se reinicie el bot (como la API)
+async fn update_settings(
+	session: AdminSession,
+	Path(chat_id): Path<ChatId>,
+	Extension(config): Extension<AppConfig>,
+	Extension(i18n): Extension<Arc<I18n>>,
+	Form(form): Form<Vec<(String, String)>>,
+) -> Result<Redirect, StatusCode> {
+	session.authorize(&config, chat_id)?;
+
+	let field = |name: &str| {
+		form.iter()
+			.find(|(key, _)| key == name)
+			.map(|(_, value)| value.trim())
+			.filter(|value| !value.is_empty())
+	};
+
+	let mut settings = config.groups_config.get(chat_id);
+	settings.chat_name = field("chat_name").map(str::to_string);
+	settings.page.description = field("description").map(str::to_string);
+	settings.verify_in_private = field("verify_in_private").is_some();
+	settings.use_web_app = field("use_web_app").is_some();
+
+	settings.language = match field("language") {
+		Some(lang) if i18n.available_languages().iter().any(|code| code == lang) => {
+			Some(lang.to_string())
+		},
+		Some(_) => return Err(StatusCode::BAD_REQUEST),
+		None => None,
+	};
+
+	if let Some(ban_after) = field("ban_after") {
+		settings.ban_after =
+			humantime::parse_duration(ban_after).map_err(|_| StatusCode::BAD_REQUEST)?;
+	}
+
+	settings.credential_types = form
+		.iter()
+		.filter(|(key, value)| key == "credential_types" && CREDENTIAL_TYPES.contains(&value.as_str()))
+		.map(|(_, value)| value.clone())
+		.collect();
+	if settings.credential_types.is_empty() {
+		return Err(StatusCode::BAD_REQUEST);
+	}
+
+	settings.validate().map_err(|e| {
+		log::warn!("Rejected settings for chat {chat_id}: {e}");
+		StatusCode::BAD_REQUEST
+	})?;
+
+	log::info!("Admin {} updated the settings of chat {chat_id}", session.user_id);
+	config.groups_config.set(chat_id, settings);
+
+	Ok(Redirect::to(&format!("/admin/chats/{chat_id}")))
+}
+
+async fn approve(
+	session: AdminSession,
+	Path((chat_id, user_id)): Path<(ChatId, UserId)>,
+	Extension(bot): Extension<Bot>,
+	Extension(config): Extension<AppConfig>,
+	Extension(join_reqs): Extension<JoinRequests>,
+	Extension(events): Extension<Events>,
+) -> Result<Redirect, StatusCode> {
+	session.authorize(&config, chat_id)?;
+	if !join_reqs.contains_key(&(chat_id, user_id)) {
+		return Err(StatusCode::NOT_FOUND);
+	}
+
+	on_verified(bot, chat_id, user_id, join_reqs, events, Actor::Dashboard(session.user_id))
+		.await
+		.map_err(|e| {
+			log::error!("Failed to approve {user_id} in {chat_id}: {e:?}");
+			StatusCode::BAD_GATEWAY
+		})?;
+
+	Ok(Redirect::to(&format!("/admin/chats/{chat_id}")))
+}
+
+async fn reject_user(
+	session: AdminSession,
+	Path((chat_id, user_id)): Path<(ChatId, UserId)>,
+	Extension(bot): Extension<Bot>,
+	Extension(config): Extension<AppConfig>,
+	Extension(join_reqs): Extension<JoinRequests>,
+	Extension(events): Extension<Events>,
+) -> Result<Redirect, StatusCode> {
+	session.authorize(&config, chat_id)?;
+	if !join_reqs.contains_key(&(chat_id, user_id)) {
+		return Err(StatusCode::NOT_FOUND);
+	}
+
+	let actor = Actor::Dashboard(session.user_id);
+	reject(&bot, chat_id, user_id, &join_reqs, &events, actor)
+		.await
+		.map_err(|e| {
+			log::error!("Failed to reject {user_id} in {chat_id}: {e:?}");
+			StatusCode::BAD_GATEWAY
+		})?;
+
+	Ok(Redirect::to(&format!("/admin/chats/{chat_id}")))
+}
+
+fn render(
+	templates: &Templates,
+	name: &str,
+	ctx: minijinja::Value,
+) -> Result<Html<String>, StatusCode> {
+	templates
+		.get_template(name)
+		.and_then(|template| template.render(ctx))
+		.map(Html)
+		.map_err(|e| {
+			log::error!("Failed to render {name}: {e:?}");
+			StatusCode::INTERNAL_SERVER_ERROR
+		})
+}
+
@@ -0,0 +1,136 @@
+use hmac::Mac;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use teloxide::types::UserId;
+
+use super::signature::{self, unix_now, HmacSha256};
+
+/// Valida los campos que el Login Widget de Telegram envía a su `data-auth-url`
+/// (https://core.telegram.org/widgets/login#checking-authorization)
+pub fn validate_login(
+	fields: &[(String, String)],
+	bot_token: &str,
+	now: u64,
+) -> Result<UserId, &'static str> {
+	let fields = signature::check_fields(fields.to_vec(), &Sha256::digest(bot_token), now)?;
+
+	fields
+		.get("id")
+		.and_then(|id| id.parse().ok())
+		.map(UserId)
+		.ok_or("missing id")
+}
+
+/// Firma una sesión del panel para `user_id`, válida durante `ttl`, como `<user_id>.<expira>.<hmac>`
+pub fn sign_session(user_id: UserId, ttl: Duration, bot_token: &str) -> String {
+	let payload = format!("{user_id}.{}", unix_now() + ttl.as_secs());
+	let signature = hex::encode(session_mac(&payload, bot_token).finalize().into_bytes());
+
+	format!("{payload}.{signature}")
+}
+
+/// Usuario de la cookie de sesión, si la firma es válida y no ha caducado
+pub fn verify_session(session: &str, bot_token: &str) -> Option<UserId> {
+	let (payload, signature) = session.rsplit_once('.')?;
+	session_mac(payload, bot_token)
+		.verify_slice(&hex::decode(signature).ok()?)
+		.ok()?;
+
+	let (user_id, expires) = payload.split_once('.')?;
+	if expires.parse::<u64>().ok()? < unix_now() {
+		return None;
+	}
+
+	user_id.parse().ok().map(UserId)
+}
+
+fn session_mac(payload: &str, bot_token: &str) -> HmacSha256 {
+	// Una clave distinta a la del Login Widget, para que su hash nunca valga como sesión
+	let mut secret =
+		HmacSha256::new_from_slice(b"AdminSession").expect("HMAC accepts any key size");
+	secret.update(bot_token.as_bytes());
+
+	let mut mac = HmacSha256::new_from_slice(&secret.finalize().into_bytes())
+		.expect("HMAC accepts any key size");
+	mac.update(payload.as_bytes());
+	mac
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const BOT_TOKEN: &str = "123456:ABC-DEF1234ghIkl-zyx57W2v1u123ew11";
+
+	/// Campos del Login Widget firmados para `BOT_TOKEN` fuera del crate, con `auth_date` 1700000000
+	fn widget_fields() -> Vec<(String, String)> {
+		[
+			("id", "42"),
+			("first_name", "Ada"),
+			("username", "ada"),
+			("auth_date", "1700000000"),
+			(
+				"hash",
+				"6c3477126e81bb583e07b1c12d0bd4accb19d6c59b185e3aea50ed68ca124c6f",
+			),
+		]
+		.into_iter()
+		.map(|(key, value)| (key.to_string(), value.to_string()))
+		.collect()
+	}
+
+	#[test]
+	fn accepts_login_signed_by_telegram() {
+		assert_eq!(
+			validate_login(&widget_fields(), BOT_TOKEN, 1_700_000_060),
+			Ok(UserId(42))
+		);
+	}
+
+	#[test]
+	fn rejects_login_for_other_bot() {
+		assert_eq!(
+			validate_login(&widget_fields(), "654321:other", 1_700_000_060),
+			Err("invalid signature")
+		);
+	}
+
+	#[test]
+	fn accepts_valid_session() {
+		let session = sign_session(UserId(42), Duration::from_secs(60), BOT_TOKEN);
+		assert_eq!(verify_session(&session, BOT_TOKEN), Some(UserId(42)));
+	}
+
+	#[test]
+	fn rejects_tampered_session() {
+		let session = sign_session(UserId(42), Duration::from_secs(60), BOT_TOKEN);
+		assert_eq!(
+			verify_session(&session.replacen("42", "43", 1), BOT_TOKEN),
+			None
+		);
+		assert_eq!(verify_session(&session, "654321:other"), None);
+		assert_eq!(verify_session("42", BOT_TOKEN), None);
+	}
+
+	#[test]
+	fn rejects_expired_session() {
+		let payload = format!("42.{}", unix_now() - 1);
+		let signature = hex::encode(session_mac(&payload, BOT_TOKEN).finalize().into_bytes());
+		assert_eq!(
+			verify_session(&format!("{payload}.{signature}"), BOT_TOKEN),
+			None
+		);
+	}
+
+	#[test]
+	fn rejects_login_hash_as_session() {
+		let payload = format!("42.{}", unix_now() + 60);
+		let mut mac = HmacSha256::new_from_slice(&Sha256::digest(BOT_TOKEN)).unwrap();
+		mac.update(payload.as_bytes());
+		let signature = hex::encode(mac.finalize().into_bytes());
+		assert_eq!(
+			verify_session(&format!("{payload}.{signature}"), BOT_TOKEN),
+			None
+		);
+	}
+}
@@ -1,25 +1,39 @@
 use axum::{
-	extract::Path,
-	http::StatusCode,
-	response::{Html, Redirect},
+	extract::{Path, Query, Request},
+	http::{header, HeaderMap, StatusCode},
+	middleware::{self, Next},
+	response::{Html, Redirect, Response},
 	routing::get,
 	Extension, Json, Router,
 };
-use indoc::formatdoc;
-use posthog_rs::Event;
+use minijinja::context;
 use serde_json::json;
 use std::sync::Arc;
-use teloxide::{
-	types::{ChatId, User, UserId},
-	Bot,
-};
+use teloxide::types::{ChatId, User, UserId};
 use tokio::{net::TcpListener, signal};
 
 use crate::{
-	bot::{on_verified, JoinRequests},
+	analytics::{AnalyticsEvent, EventKind},
+	audit::Actor,
+	bot::{on_verified, Bot, JoinRequests},
 	config::AppConfig,
+	events::Events,
 	i18n::I18n, // AÑADIR
+	metrics::Metrics,
+	permissions::PermissionMonitor,
 };
+use assets::IdKitAsset;
+use status::{VerifyResponse, VerifyStatus};
+use templates::Templates;
+
+mod admin;
+mod assets;
+mod dashboard;
+mod login;
+mod signature;
+mod status;
+mod templates;
+mod web_app;
 
 pub async fn start(
 	bot: Bot,
@@ -27,23 +41,46 @@ pub async fn start(
 	bot_data: User,
 	join_requests: JoinRequests,
 	i18n: Arc<I18n>, // AÑADIR ESTE PARÁMETRO
+	events: Events,
+	permissions: Arc<PermissionMonitor>,
 ) {
-	let app = Router::new()
+	let idkit = IdKitAsset::load(&config.idkit).expect("Failed to load IDKit bundle");
+	let templates = templates::load(&config, &idkit).expect("Failed to load templates");
+
+	let bot_username = bot_data.username.clone().unwrap();
+
+	let mut app = Router::new()
 		.route(
 			"/",
-			get(|| async {
-				Redirect::permanent(&format!("https://t.me/{}", bot_data.username.unwrap()))
-			}),
+			get(|| async move { Redirect::permanent(&format!("https://t.me/{bot_username}")) }),
 		)
 		.route("/health", get(|| async { "OK" }))
+		// Las etiquetas por grupo revelan todos los grupos que modera el bot
+		.route(
+			"/metrics",
+			get(metrics_page).route_layer(middleware::from_fn(require_metrics_token)),
+		)
+		.route("/static/idkit.js", get(assets::idkit_bundle))
 		.route(
 			"/verify/:chat_id/:user_id",
 			get(verify_page).post(verify_api),
 		)
+		.nest("/admin", dashboard::router());
+
+	if config.admin_api_key.is_some() {
+		app = app.nest("/admin/api", admin::router());
+	}
+
+	let app = app
 		.layer(Extension(bot))
+		.layer(Extension(bot_data))
 		.layer(Extension(config))
 		.layer(Extension(join_requests))
-		.layer(Extension(i18n)); // AÑADIR ESTA LÍNEA
+		.layer(Extension(i18n))
+		.layer(Extension(templates))
+		.layer(Extension(idkit))
+		.layer(Extension(events))
+		.layer(Extension(permissions));
 
 	let listener = TcpListener::bind(("0.0.0.0", 8000)).await.unwrap();
 	log::info!(
@@ -57,71 +94,81 @@ pub async fn start(
 		.unwrap();
 }
 
+/// Token propio para el scraper de métricas, distinto de la clave de administración
+async fn require_metrics_token(
+	Extension(config): Extension<AppConfig>,
+	headers: HeaderMap,
+	req: Request,
+	next: Next,
+) -> Result<Response, StatusCode> {
+	admin::check_token(&headers, config.metrics_token.as_deref())?;
+
+	Ok(next.run(req).await)
+}
+
+async fn metrics_page(
+	Extension(events): Extension<Events>,
+	Extension(join_reqs): Extension<JoinRequests>,
+) -> String {
+	events.metrics.render(&join_reqs)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct PageQuery {
+	lang: Option<String>,
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn verify_page(
+	headers: HeaderMap,
+	Query(query): Query<PageQuery>,
 	Extension(config): Extension<AppConfig>,
 	Path((chat_id, user_id)): Path<(ChatId, UserId)>,
 	Extension(join_reqs): Extension<JoinRequests>,
 	Extension(i18n): Extension<Arc<I18n>>, // AÑADIR ESTE PARÁMETRO
+	Extension(templates): Extension<Templates>,
+	Extension(events): Extension<Events>,
 ) -> Result<Html<String>, StatusCode> {
 	let join_req = join_reqs
 		.get(&(chat_id, user_id))
 		.ok_or(StatusCode::NOT_FOUND)?;
 	let msg_id = join_req.msg_id.ok_or(StatusCode::CONFLICT)?;
 	
-	// Detectar idioma del grupo o usuario
 	let group_settings = config.groups_config.get(chat_id);
-	let lang = group_settings
-		.language
-		.as_ref()
-		.map(|s| s.as_str())
-		.unwrap_or("en");
-	
+	let lang = resolve_lang(
+		&i18n,
+		&headers,
+		&config,
+		chat_id,
+		query.lang.as_deref(),
+		join_req.language_code.as_deref(),
+	);
+
 	let translation = i18n.get(lang);
+	events
+		.analytics
+		.track(AnalyticsEvent::new(EventKind::PageOpened, chat_id, user_id).lang(lang));
 
-	let page = formatdoc! {"<!DOCTYPE html>
-        <html lang=\"{lang}\">
-            <head>
-                <meta charset=\"UTF-8\" />
-                <meta http-equiv=\"X-UA-Compatible\" content=\"IE=edge\" />
-                <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\" />
-                <title>{title}</title>
-            </head>
-            <body>
-                <script src=\"https://unpkg.com/@worldcoin/idkit-standalone/build/index.global.js\"></script>
-
-                <script>
-                    IDKit.init({{
-                        autoClose: true,
-                        signal: '{msg_id}',
-                        app_id: '{app_id}',
-                        action: '{chat_id}',
-                        enableTelemetry: true,
-                        credential_types: ['phone', 'orb'],
-                    }})
-
-                    window.addEventListener('load', async () => {{
-                        const res = await fetch('/verify/{chat_id}/{user_id}', {{
-                            method: 'POST',
-                            body: JSON.stringify(await IDKit.open()),
-                            headers: {{ 'Content-Type': 'application/json' }},
-                        }})
-
-                        if (res.ok) alert('{alert_success}')
-                        else if (res.status === 429) alert('{alert_already_used}')
-                        else alert('{alert_error}')
-
-                        window.close()
-                    }})
-                </script>
-            </body>
-        </html>",
-		lang = lang,
-		title = translation.verify_button,
-		app_id = config.app_id,
-		alert_success = translation.alert_success.replace("'", "\\'"),
-		alert_already_used = translation.alert_already_used.replace("'", "\\'"),
-		alert_error = translation.alert_error.replace("'", "\\'"),
-	};
+	let page = templates
+		.get_template(&templates::verify_template(&templates, chat_id))
+		.and_then(|template| {
+			template.render(context! {
+				lang,
+				translation,
+				languages => i18n.available_languages(),
+				credential_types => &group_settings.credential_types,
+				page => &group_settings.page,
+				web_app => group_settings.use_web_app,
+				app_id => &config.app_id,
+				signal => msg_id.to_string(),
+				action => chat_id.to_string(),
+				verify_path => format!("/verify/{chat_id}/{user_id}"),
+			})
+		})
+		.map_err(|e| {
+			log::error!("Failed to render verification page: {e:?}");
+			StatusCode::INTERNAL_SERVER_ERROR
+		})?;
 
 	Ok(Html(page))
 }
@@ -132,21 +179,156 @@ struct VerifyRequest {
 	merkle_root: String,
 	nullifier_hash: String,
 	credential_type: String,
+	/// `initData` tal cual, si la página se abre como Mini App de Telegram
+	#[serde(default)]
+	init_data: Option<String>,
+	/// Idioma del mensaje de respuesta, por encima de `Accept-Language`
+	#[serde(default)]
+	lang: Option<String>,
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn verify_api(
+	headers: HeaderMap,
 	Extension(bot): Extension<Bot>,
 	Extension(config): Extension<AppConfig>,
 	Path((chat_id, user_id)): Path<(ChatId, UserId)>,
 	Extension(join_reqs): Extension<JoinRequests>,
+	Extension(i18n): Extension<Arc<I18n>>,
+	Extension(events): Extension<Events>,
 	Json(req): Json<VerifyRequest>,
-) -> Result<&'static str, StatusCode> {
-	let join_req = join_reqs
+) -> VerifyResponse {
+	let Events {
+		metrics, analytics, ..
+	} = &events;
+	metrics.verifications_started.inc();
+
+	let (language_code, pending_user) = join_reqs
 		.get(&(chat_id, user_id))
-		.ok_or(StatusCode::NOT_FOUND)?;
-	let msg_id = join_req.msg_id.ok_or(StatusCode::CONFLICT)?;
+		.map(|join_req| {
+			let pending_user = (!join_req.is_verified).then(|| join_req.user.clone());
+			(join_req.language_code.clone(), pending_user)
+		})
+		.unzip();
+	let lang = resolve_lang(
+		&i18n,
+		&headers,
+		&config,
+		chat_id,
+		req.lang.as_deref(),
+		language_code.flatten().as_deref(),
+	);
+	let translation = i18n.get(lang).clone();
 
-	let req = reqwest::Client::new()
+	let event = |kind| {
+		AnalyticsEvent::new(kind, chat_id, user_id)
+			.lang(lang)
+			.credential_type(&req.credential_type)
+	};
+	analytics.track(event(EventKind::ProofSubmitted));
+	let verified = event(EventKind::Verified);
+	let credential_type = req.credential_type.clone();
+
+	let verified_user = match verify(&config, (chat_id, user_id), &join_reqs, metrics, req).await {
+		Ok(()) => {
+			if let Some(mut join_req) = join_reqs.get_mut(&(chat_id, user_id)) {
+				join_req.credential_type.get_or_insert(credential_type);
+			}
+
+			on_verified(bot, chat_id, user_id, join_reqs, events.clone(), Actor::Bot)
+				.await
+				.map_err(|e| {
+					// La solicitud sigue pendiente, así que la página puede volver a enviarla
+					log::error!("Failed to approve verified user: {e:?}");
+					VerifyStatus::UpstreamFailure
+				})
+		},
+		Err(status) => Err(status),
+	};
+
+	let status = match verified_user {
+		Ok(()) => {
+			metrics.verifications_succeeded.inc();
+			analytics.track(verified);
+			VerifyStatus::Verified
+		},
+		Err(status) => {
+			// La ruta no tiene autenticación: solo se avisa de los intentos sobre solicitudes pendientes
+			match (status, pending_user.flatten()) {
+				(VerifyStatus::NotFound | VerifyStatus::AlreadyVerified, _) | (_, None) => metrics
+					.verifications_failed
+					.with_label_values(&[status.as_str()])
+					.inc(),
+				(_, Some(user)) => events.failed(chat_id, &user, status.as_str()),
+			}
+			status
+		},
+	};
+
+	VerifyResponse::new(status, &translation)
+}
+
+/// Comprueba la solicitud de unión y valida la prueba con el Developer Portal
+async fn verify(
+	config: &AppConfig,
+	(chat_id, user_id): (ChatId, UserId),
+	join_reqs: &JoinRequests,
+	metrics: &Metrics,
+	req: VerifyRequest,
+) -> Result<(), VerifyStatus> {
+	let group_settings = config.groups_config.get(chat_id);
+	// Se copia lo necesario para no bloquear la solicitud mientras responde el Developer Portal
+	let (is_verified, is_expired, msg_id, proof_accepted) = join_reqs
+		.get(&(chat_id, user_id))
+		.map(|join_req| {
+			(
+				join_req.is_verified,
+				join_req.is_expired(),
+				join_req.msg_id,
+				join_req.credential_type.is_some(),
+			)
+		})
+		.ok_or(VerifyStatus::NotFound)?;
+
+	if is_verified {
+		return Err(VerifyStatus::AlreadyVerified);
+	}
+	if is_expired {
+		return Err(VerifyStatus::Expired);
+	}
+	let msg_id = msg_id.ok_or(VerifyStatus::AlreadyVerified)?;
+
+	match req.init_data.as_deref() {
+		Some(init_data) => {
+			let now = signature::unix_now();
+			let web_app_user = web_app::validate_init_data(init_data, &config.bot_token, now)
+				.map_err(|e| {
+					log::warn!("Rejected Mini App init data: {e}");
+					VerifyStatus::Unauthorized
+				})?;
+
+			if web_app_user.id != user_id {
+				return Err(VerifyStatus::Unauthorized);
+			}
+		},
+		None if group_settings.use_web_app => {
+			return Err(VerifyStatus::Unauthorized);
+		},
+		None => {},
+	}
+
+	// La prueba ya se aceptó y solo falló levantar la restricción; el Developer Portal ahora
+	// rechazaría el nullifier como reutilizado, así que el reintento va directo a aprobar
+	if proof_accepted {
+		return Ok(());
+	}
+
+	if !group_settings.credential_types.contains(&req.credential_type) {
+		return Err(VerifyStatus::InsufficientCredential);
+	}
+
+	let timer = metrics.developer_portal_latency.start_timer();
+	let res = reqwest::Client::new()
 		.post(format!(
 			"https://developer.worldcoin.org/api/v1/verify/{}",
 			config.app_id
@@ -162,41 +344,58 @@ async fn verify_api(
 		}))
 		.send()
 		.await
-		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+		.map_err(|e| {
+			log::error!("Failed to reach the Developer Portal: {e:?}");
+			VerifyStatus::UpstreamFailure
+		})?;
+	timer.observe_duration();
 
-	if req.status().is_client_error() || req.status().is_server_error() {
-		let res = req.json::<serde_json::Value>().await.map_err(|e| {
+	if res.status().is_client_error() || res.status().is_server_error() {
+		let is_server_error = res.status().is_server_error();
+		let res = res.json::<serde_json::Value>().await.map_err(|e| {
 			log::error!("Failed to deserialize dev portal body: {e:?}");
-			StatusCode::INTERNAL_SERVER_ERROR
+			VerifyStatus::UpstreamFailure
 		})?;
 
 		let Some(code) = res.get("code") else {
 			log::error!("Developer Portal returned error: {:?}", res);
-			return Err(StatusCode::BAD_REQUEST);
+			return Err(if is_server_error {
+				VerifyStatus::UpstreamFailure
+			} else {
+				VerifyStatus::InvalidProof
+			});
 		};
 
 		if code.as_str() == Some("max_verifications_reached") {
-			return Err(StatusCode::TOO_MANY_REQUESTS);
+			return Err(VerifyStatus::NullifierReused);
 		}
 
 		log::error!("Failed to verify proof: {:?}", res);
-		return Err(StatusCode::BAD_REQUEST);
+		return Err(VerifyStatus::InvalidProof);
 	}
 
-	drop(join_req);
-
-	on_verified(bot, chat_id, user_id, join_reqs)
-		.await
-		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-	if let Some(posthog) = config.posthog() {
-		let event = Event::new("telegram integration verification", &user_id.to_string());
+	Ok(())
+}
 
-		posthog.capture(event).map_err(|e| {
-			log::error!("Failed to send event to PostHog: {e:?}");
-			StatusCode::INTERNAL_SERVER_ERROR
-		})?;
-	}
+/// Idioma elegido explícitamente, luego el del grupo, el de Telegram del usuario,
+/// el del navegador y por último el idioma por defecto
+fn resolve_lang<'a>(
+	i18n: &'a I18n,
+	headers: &HeaderMap,
+	config: &AppConfig,
+	chat_id: ChatId,
+	requested: Option<&str>,
+	language_code: Option<&str>,
+) -> &'a str {
+	let accept_language = headers
+		.get(header::ACCEPT_LANGUAGE)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| i18n.parse_accept_language(value));
 
-	Ok("Verified!")
+	i18n.resolve([
+		requested,
+		config.groups_config.get(chat_id).language.as_deref(),
+		language_code.and_then(|code| code.split('-').next()),
+		accept_language,
+	])
 }
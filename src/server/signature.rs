@@ -0,0 +1,152 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub type HmacSha256 = Hmac<Sha256>;
+
+/// Tiempo durante el que se acepta un `auth_date` firmado por Telegram
+const MAX_AGE: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// Campos firmados por Telegram, ya comprobados
+#[derive(Debug)]
+pub struct SignedFields(Vec<(String, String)>);
+
+impl SignedFields {
+	pub fn get(&self, name: &str) -> Option<&str> {
+		self.0
+			.iter()
+			.find(|(key, _)| key == name)
+			.map(|(_, value)| value.as_str())
+	}
+}
+
+/// Comprueba el `hash` de los campos con la clave `secret` y que `auth_date` no sea más antiguo
+/// que `MAX_AGE` en `now`. Es la comprobación común del Login Widget y de las Mini Apps, que solo
+/// cambian en cómo se deriva la clave del token del bot
+pub fn check_fields(
+	fields: impl IntoIterator<Item = (String, String)>,
+	secret: &[u8],
+	now: u64,
+) -> Result<SignedFields, &'static str> {
+	let mut hash = None;
+	let mut fields = fields
+		.into_iter()
+		.filter_map(|(key, value)| {
+			if key == "hash" {
+				hash = Some(value);
+				return None;
+			}
+			Some((key, value))
+		})
+		.collect::<Vec<_>>();
+
+	let hash = hex::decode(hash.ok_or("missing hash")?).map_err(|_| "malformed hash")?;
+
+	fields.sort();
+	let data_check_string = fields
+		.iter()
+		.map(|(key, value)| format!("{key}={value}"))
+		.collect::<Vec<_>>()
+		.join("\n");
+
+	let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key size");
+	mac.update(data_check_string.as_bytes());
+	mac.verify_slice(&hash).map_err(|_| "invalid signature")?;
+
+	let fields = SignedFields(fields);
+	let auth_date = fields
+		.get("auth_date")
+		.and_then(|date| date.parse::<u64>().ok())
+		.ok_or("missing auth_date")?;
+	if now.saturating_sub(auth_date) > MAX_AGE.as_secs() {
+		return Err("expired auth_date");
+	}
+
+	Ok(fields)
+}
+
+pub fn unix_now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SECRET: &[u8] = b"secret";
+	const AUTH_DATE: u64 = 1_700_000_000;
+
+	/// Campos de ejemplo con el `hash` calculado con `secret`
+	fn signed(secret: &[u8]) -> Vec<(String, String)> {
+		let mut mac = HmacSha256::new_from_slice(secret).unwrap();
+		mac.update(format!("auth_date={AUTH_DATE}\nid=42\nname=Ada").as_bytes());
+
+		[
+			("name", "Ada".to_string()),
+			("id", "42".to_string()),
+			("auth_date", AUTH_DATE.to_string()),
+			("hash", hex::encode(mac.finalize().into_bytes())),
+		]
+		.into_iter()
+		.map(|(key, value)| (key.to_string(), value))
+		.collect()
+	}
+
+	#[test]
+	fn accepts_signed_fields_in_any_order() {
+		let mut fields = signed(SECRET);
+		fields.reverse();
+
+		let fields = check_fields(fields, SECRET, AUTH_DATE).unwrap();
+		assert_eq!(fields.get("id"), Some("42"));
+		assert_eq!(fields.get("hash"), None);
+	}
+
+	#[test]
+	fn rejects_tampered_field() {
+		let mut fields = signed(SECRET);
+		fields[1].1 = "43".to_string();
+		assert_eq!(
+			check_fields(fields, SECRET, AUTH_DATE).unwrap_err(),
+			"invalid signature"
+		);
+	}
+
+	#[test]
+	fn rejects_other_secret() {
+		let fields = signed(b"other");
+		assert_eq!(
+			check_fields(fields, SECRET, AUTH_DATE).unwrap_err(),
+			"invalid signature"
+		);
+	}
+
+	#[test]
+	fn rejects_missing_or_malformed_hash() {
+		let mut fields = signed(SECRET);
+		fields.pop();
+		assert_eq!(
+			check_fields(fields.clone(), SECRET, AUTH_DATE).unwrap_err(),
+			"missing hash"
+		);
+
+		fields.push(("hash".to_string(), "not hex".to_string()));
+		assert_eq!(
+			check_fields(fields, SECRET, AUTH_DATE).unwrap_err(),
+			"malformed hash"
+		);
+	}
+
+	#[test]
+	fn checks_auth_date_age() {
+		let max_age = MAX_AGE.as_secs();
+		assert!(check_fields(signed(SECRET), SECRET, AUTH_DATE + max_age).is_ok());
+		assert_eq!(
+			check_fields(signed(SECRET), SECRET, AUTH_DATE + max_age + 1).unwrap_err(),
+			"expired auth_date"
+		);
+	}
+}
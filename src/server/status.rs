@@ -0,0 +1,88 @@
+use axum::{
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use serde::Serialize;
+
+use crate::i18n::Translation;
+
+/// Resultado de un intento de verificación, devuelto como `{ "code": ..., "message": ... }`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyStatus {
+	Verified,
+	Expired,
+	NotFound,
+	InvalidProof,
+	Unauthorized,
+	AlreadyVerified,
+	NullifierReused,
+	UpstreamFailure,
+	InsufficientCredential,
+}
+
+impl VerifyStatus {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::Verified => "verified",
+			Self::Expired => "expired",
+			Self::NotFound => "not_found",
+			Self::InvalidProof => "invalid_proof",
+			Self::Unauthorized => "unauthorized",
+			Self::AlreadyVerified => "already_verified",
+			Self::NullifierReused => "nullifier_reused",
+			Self::UpstreamFailure => "upstream_failure",
+			Self::InsufficientCredential => "insufficient_credential",
+		}
+	}
+
+	pub fn status_code(self) -> StatusCode {
+		match self {
+			Self::Verified => StatusCode::OK,
+			Self::Expired => StatusCode::GONE,
+			Self::NotFound => StatusCode::NOT_FOUND,
+			Self::InvalidProof => StatusCode::BAD_REQUEST,
+			Self::Unauthorized => StatusCode::UNAUTHORIZED,
+			Self::AlreadyVerified => StatusCode::CONFLICT,
+			Self::NullifierReused => StatusCode::TOO_MANY_REQUESTS,
+			Self::UpstreamFailure => StatusCode::BAD_GATEWAY,
+			Self::InsufficientCredential => StatusCode::FORBIDDEN,
+		}
+	}
+
+	pub fn message(self, translation: &Translation) -> &str {
+		match self {
+			Self::Verified => &translation.alert_success,
+			Self::Expired => &translation.error_expired,
+			Self::NotFound => &translation.error_not_found,
+			Self::InvalidProof => &translation.error_invalid_proof,
+			Self::Unauthorized => &translation.error_unauthorized,
+			Self::AlreadyVerified => &translation.error_already_verified,
+			Self::NullifierReused => &translation.alert_already_used,
+			Self::UpstreamFailure => &translation.alert_error,
+			Self::InsufficientCredential => &translation.error_insufficient_credential,
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerifyResponse {
+	code: VerifyStatus,
+	message: String,
+}
+
+impl VerifyResponse {
+	pub fn new(code: VerifyStatus, translation: &Translation) -> Self {
+		Self {
+			code,
+			message: code.message(translation).to_string(),
+		}
+	}
+}
+
+impl IntoResponse for VerifyResponse {
+	fn into_response(self) -> Response {
+		(self.code.status_code(), Json(self)).into_response()
+	}
+}
@@ -0,0 +1,57 @@
+use minijinja::{Environment, Value};
+use std::{error::Error, fs, sync::Arc};
+use teloxide::types::ChatId;
+
+use super::assets::IdKitAsset;
+use crate::config::AppConfig;
+
+pub type Templates = Arc<Environment<'static>>;
+
+const VERIFY_TEMPLATE: &str = include_str!("templates/verify.html");
+
+/// Plantillas del panel de administración, que no se pueden reemplazar
+const ADMIN_TEMPLATES: &[(&str, &str)] = &[
+	("admin/base.html", include_str!("templates/admin/base.html")),
+	("admin/activity.html", include_str!("templates/admin/activity.html")),
+	("admin/login.html", include_str!("templates/admin/login.html")),
+	("admin/index.html", include_str!("templates/admin/index.html")),
+	("admin/chat.html", include_str!("templates/admin/chat.html")),
+];
+
+/// Entorno de plantillas con la página de verificación por defecto, el panel y las de cada
+/// grupo configuradas en `page.template`
+pub fn load(config: &AppConfig, idkit: &IdKitAsset) -> Result<Templates, Box<dyn Error>> {
+	let mut env = Environment::new();
+	env.add_global("idkit", Value::from_serialize(idkit));
+	env.add_template("verify.html", VERIFY_TEMPLATE)?;
+
+	for (name, source) in ADMIN_TEMPLATES {
+		env.add_template(name, source)?;
+	}
+
+	for (chat_id, settings) in config.groups_config.groups() {
+		if let Some(path) = &settings.page.template {
+			let source = fs::read_to_string(path)
+				.map_err(|e| format!("Failed to read template {}: {e}", path.display()))?;
+
+			env.add_template_owned(verify_name(chat_id), source)?;
+		}
+	}
+
+	Ok(Arc::new(env))
+}
+
+/// Nombre de la plantilla de verificación del grupo
+pub fn verify_template(templates: &Templates, chat_id: ChatId) -> String {
+	let name = verify_name(chat_id);
+
+	if templates.get_template(&name).is_ok() {
+		name
+	} else {
+		"verify.html".to_string()
+	}
+}
+
+fn verify_name(chat_id: ChatId) -> String {
+	format!("verify/{chat_id}.html")
+}
@@ -0,0 +1,51 @@
+use hmac::Mac;
+use serde::Deserialize;
+use teloxide::types::UserId;
+
+use super::signature::{self, HmacSha256};
+
+#[derive(Debug, Deserialize)]
+pub struct WebAppUser {
+	pub id: UserId,
+}
+
+/// Valida el `initData` que envía la Mini App de Telegram
+/// (https://core.telegram.org/bots/webapps#validating-data-received-via-the-mini-app)
+pub fn validate_init_data(
+	init_data: &str,
+	bot_token: &str,
+	now: u64,
+) -> Result<WebAppUser, &'static str> {
+	let mut secret = HmacSha256::new_from_slice(b"WebAppData").expect("HMAC accepts any key size");
+	secret.update(bot_token.as_bytes());
+
+	let fields = url::form_urlencoded::parse(init_data.as_bytes()).into_owned();
+	let fields = signature::check_fields(fields, &secret.finalize().into_bytes(), now)?;
+
+	serde_json::from_str(fields.get("user").ok_or("missing user")?).map_err(|_| "malformed user")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const BOT_TOKEN: &str = "123456:ABC-DEF1234ghIkl-zyx57W2v1u123ew11";
+
+	/// `initData` de ejemplo firmado para `BOT_TOKEN` fuera del crate, con `auth_date` 1700000000
+	const INIT_DATA: &str = "query_id=AAHdF6IQAAAAAN0XohDhrOrc\
+		&user=%7B%22id%22%3A42%2C%22first_name%22%3A%22Ada%22%7D\
+		&auth_date=1700000000\
+		&hash=73925b67c982b06d0102c2e0e912872281ce4ecabcd2a204e57678b444f0a6f3";
+
+	#[test]
+	fn accepts_init_data_signed_by_telegram() {
+		let user = validate_init_data(INIT_DATA, BOT_TOKEN, 1_700_000_060).unwrap();
+		assert_eq!(user.id, UserId(42));
+	}
+
+	#[test]
+	fn rejects_init_data_for_other_bot() {
+		let err = validate_init_data(INIT_DATA, "654321:other", 1_700_000_060).unwrap_err();
+		assert_eq!(err, "invalid signature");
+	}
+}
@@ -0,0 +1,180 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+	collections::VecDeque,
+	sync::{Arc, Mutex},
+	time::{Duration, SystemTime},
+};
+use teloxide::types::{ChatId, UserId};
+use tokio::time::sleep;
+
+use crate::{bot::JoinRequest, config::AppConfig};
+
+/// Cuántas entregas se guardan en el registro en memoria
+const LOG_CAPACITY: usize = 1000;
+const MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+	Verified,
+	Removed,
+}
+
+/// Cuerpo enviado a cada webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+	pub id: String,
+	pub event: WebhookEvent,
+	pub chat_id: ChatId,
+	pub user_id: UserId,
+	pub reason: Option<String>,
+	#[serde(with = "humantime_serde")]
+	pub timestamp: SystemTime,
+}
+
+/// Entrada del registro de entregas
+#[derive(Debug, Clone, Serialize)]
+pub struct Delivery {
+	pub id: String,
+	pub url: String,
+	pub event: WebhookEvent,
+	pub chat_id: ChatId,
+	pub user_id: UserId,
+	pub attempts: u32,
+	pub delivered: bool,
+	pub status: Option<u16>,
+	pub error: Option<String>,
+	#[serde(with = "humantime_serde")]
+	pub timestamp: SystemTime,
+}
+
+/// Webhooks salientes firmados con HMAC-SHA256, globales o por grupo
+pub struct Webhooks {
+	config: AppConfig,
+	client: reqwest::Client,
+	deliveries: Mutex<VecDeque<Delivery>>,
+}
+
+impl Webhooks {
+	pub fn new(config: AppConfig) -> Self {
+		Self {
+			config,
+			client: reqwest::Client::builder()
+				.timeout(Duration::from_secs(10))
+				.build()
+				.expect("Failed to build HTTP client"),
+			deliveries: Mutex::new(VecDeque::with_capacity(LOG_CAPACITY)),
+		}
+	}
+
+	/// Envía el evento a todos los webhooks que aplican, en segundo plano
+	pub fn dispatch(
+		self: &Arc<Self>,
+		event: WebhookEvent,
+		chat_id: ChatId,
+		user_id: UserId,
+		reason: Option<&str>,
+	) {
+		let group_webhooks = &self.config.groups_config.get(chat_id).webhooks;
+
+		for webhook in self.config.webhooks.iter().chain(group_webhooks) {
+			if !webhook.events.is_empty() && !webhook.events.contains(&event) {
+				continue;
+			}
+
+			let payload = WebhookPayload {
+				event,
+				chat_id,
+				user_id,
+				id: JoinRequest::generate_token(),
+				timestamp: SystemTime::now(),
+				reason: reason.map(str::to_string),
+			};
+
+			// Los webhooks sin secreto se rechazan al validar la configuración
+			let Some(secret) = webhook.secret.clone() else {
+				continue;
+			};
+
+			tokio::spawn({
+				let webhooks = self.clone();
+				let url = webhook.url.clone();
+
+				async move { webhooks.deliver(url, &secret, payload).await }
+			});
+		}
+	}
+
+	/// Últimas entregas, de la más reciente a la más antigua
+	pub fn deliveries(&self) -> Vec<Delivery> {
+		self.deliveries.lock().unwrap().iter().rev().cloned().collect()
+	}
+
+	async fn deliver(&self, url: url::Url, secret: &str, payload: WebhookPayload) {
+		let body = serde_json::to_vec(&payload).expect("payload is always serializable");
+		let signature = sign(secret, &body);
+
+		let mut attempts = 0;
+		let (status, error) = loop {
+			attempts += 1;
+
+			let result = self
+				.client
+				.post(url.clone())
+				.header("User-Agent", "World ID Telegram Bot/1.0")
+				.header("Content-Type", "application/json")
+				.header("X-Webhook-Id", &payload.id)
+				.header("X-Signature-256", format!("sha256={signature}"))
+				.body(body.clone())
+				.send()
+				.await
+				.and_then(|res| res.error_for_status());
+
+			let (status, error) = match result {
+				Ok(res) => break (Some(res.status().as_u16()), None),
+				Err(e) => (e.status().map(|status| status.as_u16()), Some(e.to_string())),
+			};
+
+			if attempts >= MAX_ATTEMPTS {
+				break (status, error);
+			}
+
+			// 1s, 2s, 4s, 8s...
+			sleep(Duration::from_secs(1 << (attempts - 1))).await;
+		};
+
+		match &error {
+			Some(error) => log::error!(
+				"Webhook {} to {url} failed after {attempts} attempts: {error}",
+				payload.id
+			),
+			None => log::info!("Webhook {} delivered to {url}", payload.id),
+		}
+
+		let mut deliveries = self.deliveries.lock().unwrap();
+		if deliveries.len() == LOG_CAPACITY {
+			deliveries.pop_front();
+		}
+		deliveries.push_back(Delivery {
+			status,
+			attempts,
+			id: payload.id,
+			url: url.to_string(),
+			event: payload.event,
+			chat_id: payload.chat_id,
+			user_id: payload.user_id,
+			delivered: error.is_none(),
+			error,
+			timestamp: SystemTime::now(),
+		});
+	}
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+	let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key size");
+	mac.update(body);
+
+	hex::encode(mac.finalize().into_bytes())
+}